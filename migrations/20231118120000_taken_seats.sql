@@ -0,0 +1,44 @@
+-- number of `term_subscribers` of each term subject, maintained by the triggers below
+-- the check guarantees a section can never be over-subscribed
+ALTER TABLE term_subjects
+  ADD COLUMN taken_seats INTEGER NOT NULL DEFAULT 0 CHECK (taken_seats BETWEEN 0 AND max_seats);
+
+UPDATE term_subjects
+SET taken_seats = (
+  SELECT COUNT(*)
+  FROM term_subscribers
+  WHERE term_subscribers.term_subject_id = term_subjects.id
+);
+
+CREATE TRIGGER IF NOT EXISTS
+  take_seat
+AFTER INSERT ON term_subscribers
+FOR EACH ROW
+BEGIN
+  UPDATE term_subjects
+  SET taken_seats = taken_seats + 1
+  WHERE id = NEW.term_subject_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS
+  release_seat
+AFTER DELETE ON term_subscribers
+FOR EACH ROW
+BEGIN
+  UPDATE term_subjects
+  SET taken_seats = taken_seats - 1
+  WHERE id = OLD.term_subject_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS
+  move_seat
+AFTER UPDATE OF term_subject_id ON term_subscribers
+FOR EACH ROW
+BEGIN
+  UPDATE term_subjects
+  SET taken_seats = taken_seats - 1
+  WHERE id = OLD.term_subject_id;
+  UPDATE term_subjects
+  SET taken_seats = taken_seats + 1
+  WHERE id = NEW.term_subject_id;
+END;
@@ -0,0 +1,13 @@
+-- students waiting for a seat in a full term subject
+-- a student's position in the queue is determined by `id`
+CREATE TABLE IF NOT EXISTS
+  waitlist (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    student_id INTEGER NOT NULL REFERENCES users (id),
+    term_subject_id INTEGER NOT NULL REFERENCES term_subjects (id),
+    joined_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (student_id, term_subject_id)
+  ) STRICT;
+
+CREATE INDEX IF NOT EXISTS
+  waitlist_queue ON waitlist (term_subject_id, id);
@@ -0,0 +1,27 @@
+CREATE TABLE IF NOT EXISTS
+  registration_periods (
+    id INTEGER PRIMARY KEY,
+    term TEXT NOT NULL,
+    opens_at TEXT NOT NULL,
+    closes_at TEXT NOT NULL,
+    -- subjects can still be added or dropped after registration closes,
+    -- until the deadline
+    add_drop_deadline TEXT,
+    CHECK (unixepoch(opens_at) < unixepoch(closes_at)),
+    CHECK (
+      add_drop_deadline IS NULL
+      OR unixepoch(closes_at) <= unixepoch(add_drop_deadline)
+    )
+  ) STRICT;
+
+-- staggered starts, the most specific matching row overrides the `opens_at`
+-- of the period for the matching students
+CREATE TABLE IF NOT EXISTS
+  registration_start_times (
+    period_id INTEGER NOT NULL REFERENCES registration_periods (id),
+    level INTEGER,
+    program_id INTEGER REFERENCES programs (id),
+    opens_at TEXT NOT NULL,
+    CHECK (level IS NOT NULL OR program_id IS NOT NULL),
+    UNIQUE (period_id, level, program_id)
+  ) STRICT;
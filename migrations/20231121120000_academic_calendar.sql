@@ -0,0 +1,27 @@
+-- dates are ISO 8601, i.e. 'YYYY-MM-DD'
+CREATE TABLE IF NOT EXISTS
+  academic_terms (
+    id INTEGER PRIMARY KEY,
+    -- e.g "Fall 2023", same as `completed.term_abs`
+    abs TEXT NOT NULL UNIQUE,
+    starts_on TEXT NOT NULL CHECK (date(starts_on) = starts_on),
+    ends_on TEXT NOT NULL CHECK (date(ends_on) = ends_on),
+    -- first day of the first week, week parity is counted from here
+    week_one TEXT NOT NULL CHECK (date(week_one) = week_one),
+    CHECK (starts_on <= week_one AND week_one <= ends_on)
+  ) STRICT;
+
+-- holidays and exam weeks, no classes are held during either
+CREATE TABLE IF NOT EXISTS
+  calendar_events (
+    id INTEGER PRIMARY KEY,
+    term_id INTEGER NOT NULL REFERENCES academic_terms (id),
+    kind TEXT NOT NULL CHECK (kind IN ('holiday', 'exams')),
+    name TEXT NOT NULL,
+    starts_on TEXT NOT NULL CHECK (date(starts_on) = starts_on),
+    ends_on TEXT NOT NULL CHECK (date(ends_on) = ends_on),
+    CHECK (starts_on <= ends_on)
+  ) STRICT;
+
+CREATE INDEX IF NOT EXISTS
+  calendar_events_term ON calendar_events (term_id, starts_on);
@@ -0,0 +1,7 @@
+-- secret tokens of the timetable subscription feeds, one per student
+CREATE TABLE IF NOT EXISTS
+  calendar_tokens (
+    student_id INTEGER NOT NULL PRIMARY KEY REFERENCES users (id),
+    token TEXT NOT NULL UNIQUE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+  ) STRICT;
@@ -0,0 +1,3 @@
+-- which attempt of a repeated subject counts towards the cumulative GPA
+ALTER TABLE programs
+ADD COLUMN repeat_policy TEXT NOT NULL DEFAULT 'best' CHECK (repeat_policy IN ('best', 'latest'));
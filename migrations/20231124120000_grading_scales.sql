@@ -0,0 +1,71 @@
+-- maps the total mark of a subject to a letter grade and its points,
+-- one scale per bylaw
+CREATE TABLE IF NOT EXISTS
+  grading_scales (
+    id INTEGER PRIMARY KEY,
+    by_law INTEGER NOT NULL,
+    letter TEXT NOT NULL,
+    -- inclusive lower bound of the total mark, out of 100
+    min_mark REAL NOT NULL CHECK (min_mark BETWEEN 0 AND 100),
+    points REAL NOT NULL CHECK (points >= 0),
+    passing INTEGER NOT NULL CHECK (passing IN (0, 1)),
+    UNIQUE (by_law, letter),
+    UNIQUE (by_law, min_mark)
+  ) STRICT;
+
+-- the default scale for the existing bylaws
+INSERT OR IGNORE INTO grading_scales (by_law, letter, min_mark, points, passing)
+SELECT p.by_law, s.column1, s.column2, s.column3, s.column4
+FROM (SELECT DISTINCT by_law FROM programs) AS p
+CROSS JOIN (
+  VALUES
+    ('A+', 97, 4.0, 1),
+    ('A', 93, 4.0, 1),
+    ('A-', 89, 3.7, 1),
+    ('B+', 84, 3.3, 1),
+    ('B', 80, 3.0, 1),
+    ('B-', 76, 2.7, 1),
+    ('C+', 73, 2.3, 1),
+    ('C', 70, 2.0, 1),
+    ('C-', 67, 1.7, 1),
+    ('D+', 64, 1.3, 1),
+    ('D', 60, 1.0, 1),
+    ('F', 0, 0.0, 0)
+) AS s;
+
+-- the component marks, the grade is computed from their sum using the scale
+-- of `by_law`, `gpa` is only used by rows graded before marks were stored
+ALTER TABLE completed ADD COLUMN coursework REAL CHECK (coursework >= 0);
+ALTER TABLE completed ADD COLUMN midterm REAL CHECK (midterm >= 0);
+ALTER TABLE completed ADD COLUMN final_exam REAL CHECK (
+  final_exam >= 0 AND coursework + midterm + final_exam <= 100
+);
+-- the bylaw the subject was graded under, so changing the bylaw of a program
+-- doesn't affect historic grades
+ALTER TABLE completed ADD COLUMN by_law INTEGER;
+
+UPDATE completed
+SET by_law = (
+  SELECT p.by_law
+  FROM users AS u
+  INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+  INNER JOIN programs AS p ON p.id = sp.program_id
+  WHERE u.id = completed.student_id
+);
+
+CREATE TRIGGER IF NOT EXISTS
+  completed_by_law
+  AFTER INSERT ON completed
+  FOR EACH ROW
+  WHEN NEW.by_law IS NULL
+    BEGIN
+      UPDATE completed
+      SET by_law = (
+        SELECT p.by_law
+        FROM users AS u
+        INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+        INNER JOIN programs AS p ON p.id = sp.program_id
+        WHERE u.id = NEW.student_id
+      )
+      WHERE id = NEW.id;
+    END;
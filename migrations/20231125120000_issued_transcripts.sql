@@ -0,0 +1,10 @@
+-- every unofficial transcript handed out, so recipients can verify them
+CREATE TABLE IF NOT EXISTS
+  issued_transcripts (
+    -- HMAC of the transcript, see `grades::transcript`
+    verification TEXT NOT NULL PRIMARY KEY,
+    student_id INTEGER NOT NULL REFERENCES users (id),
+    issued_at TEXT NOT NULL,
+    -- the transcript as served in JSON
+    body TEXT NOT NULL CHECK (json_valid(body))
+  ) STRICT;
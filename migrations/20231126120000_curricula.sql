@@ -0,0 +1,29 @@
+-- the graduation requirements of a program under a bylaw
+CREATE TABLE IF NOT EXISTS
+  curricula (
+    id INTEGER PRIMARY KEY,
+    program_id INTEGER NOT NULL REFERENCES programs (id),
+    by_law INTEGER NOT NULL,
+    total_credits INTEGER NOT NULL CHECK (total_credits > 0),
+    UNIQUE (program_id, by_law)
+  ) STRICT;
+
+-- every subject of a required group must be passed, elective pools need
+-- `min_credits` from any of their subjects
+CREATE TABLE IF NOT EXISTS
+  curriculum_groups (
+    id INTEGER PRIMARY KEY,
+    curriculum_id INTEGER NOT NULL REFERENCES curricula (id),
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL CHECK (kind IN ('required', 'elective')),
+    -- the level the group belongs to, NULL if it spans levels
+    level INTEGER,
+    min_credits INTEGER NOT NULL DEFAULT 0 CHECK (min_credits >= 0)
+  ) STRICT;
+
+CREATE TABLE IF NOT EXISTS
+  curriculum_subjects (
+    group_id INTEGER NOT NULL REFERENCES curriculum_groups (id),
+    subject_id INTEGER NOT NULL REFERENCES subjects (id),
+    PRIMARY KEY (group_id, subject_id)
+  ) STRICT;
@@ -0,0 +1,27 @@
+-- the credit hours a student may register in a term, one policy per bylaw
+CREATE TABLE IF NOT EXISTS
+  credit_load_policies (
+    by_law INTEGER PRIMARY KEY,
+    min_credits INTEGER NOT NULL CHECK (min_credits >= 0),
+    max_credits INTEGER NOT NULL CHECK (max_credits >= min_credits),
+    -- students with a cumulative GPA of at least `overload_gpa`
+    -- may register up to `overload_credits`
+    overload_gpa REAL NOT NULL,
+    overload_credits INTEGER NOT NULL CHECK (overload_credits >= max_credits),
+    -- students with a cumulative GPA below `probation_gpa`
+    -- may only register up to `probation_credits`
+    probation_gpa REAL NOT NULL CHECK (probation_gpa <= overload_gpa),
+    probation_credits INTEGER NOT NULL CHECK (probation_credits <= max_credits),
+    -- students that can graduate within `max_credits + graduating_extra`
+    -- may register all their remaining credits, and are exempt from the minimum
+    graduating_extra INTEGER NOT NULL DEFAULT 3 CHECK (graduating_extra >= 0)
+  ) STRICT;
+
+-- the default policy for the existing bylaws
+INSERT OR IGNORE INTO credit_load_policies (
+  by_law, min_credits, max_credits,
+  overload_gpa, overload_credits,
+  probation_gpa, probation_credits
+)
+SELECT DISTINCT by_law, 12, 18, 3.0, 21, 2.0, 12
+FROM programs;
@@ -0,0 +1,8 @@
+-- pre-requisite expressions (OR groups, co-requisites, minimum credits, level
+-- or grade), overrides `pre_req` if set.
+-- e.g. {"all": [{"passed": 1}, {"any": [{"co_req": 2}, {"credits": 60}]}]}
+ALTER TABLE subjects
+ADD COLUMN pre_req_expr JSON CHECK (
+  pre_req_expr IS NULL
+  OR json_type(pre_req_expr) IN ('object', 'array')
+);
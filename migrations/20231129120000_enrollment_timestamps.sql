@@ -0,0 +1,59 @@
+-- `registered_at` keeps the time a student registered in a term subject, rows
+-- are only inserted, deleted or moved to another section of the same subject.
+-- sqlite can't add a column with a non-constant default, so the table is rebuilt
+CREATE TABLE IF NOT EXISTS
+  term_subscribers_new (
+    student_id INTEGER NOT NULL REFERENCES users(id),
+    term_subject_id INTEGER NOT NULL REFERENCES term_subjects(id),
+    registered_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE (student_id, term_subject_id)
+  ) STRICT;
+
+INSERT OR IGNORE INTO term_subscribers_new (student_id, term_subject_id)
+SELECT student_id, term_subject_id
+FROM term_subscribers;
+
+-- also drops the seat triggers
+DROP TABLE term_subscribers;
+ALTER TABLE term_subscribers_new RENAME TO term_subscribers;
+
+-- duplicates were dropped above
+UPDATE term_subjects
+SET taken_seats = (
+  SELECT COUNT(*)
+  FROM term_subscribers
+  WHERE term_subscribers.term_subject_id = term_subjects.id
+);
+
+CREATE TRIGGER IF NOT EXISTS
+  take_seat
+AFTER INSERT ON term_subscribers
+FOR EACH ROW
+BEGIN
+  UPDATE term_subjects
+  SET taken_seats = taken_seats + 1
+  WHERE id = NEW.term_subject_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS
+  release_seat
+AFTER DELETE ON term_subscribers
+FOR EACH ROW
+BEGIN
+  UPDATE term_subjects
+  SET taken_seats = taken_seats - 1
+  WHERE id = OLD.term_subject_id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS
+  move_seat
+AFTER UPDATE OF term_subject_id ON term_subscribers
+FOR EACH ROW
+BEGIN
+  UPDATE term_subjects
+  SET taken_seats = taken_seats - 1
+  WHERE id = OLD.term_subject_id;
+  UPDATE term_subjects
+  SET taken_seats = taken_seats + 1
+  WHERE id = NEW.term_subject_id;
+END;
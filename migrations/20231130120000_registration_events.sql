@@ -0,0 +1,35 @@
+-- every change to a student's registration, kept for dispute resolution.
+-- rows are written in the same transaction as the change and never modified
+CREATE TABLE IF NOT EXISTS
+  registration_events (
+    id INTEGER PRIMARY KEY,
+    student_id INTEGER NOT NULL REFERENCES users (id),
+    -- the choice added or dropped, the one swapped to for swaps
+    term_subject_id INTEGER NOT NULL REFERENCES term_subjects (id),
+    action TEXT NOT NULL CHECK (action IN ('add', 'drop', 'swap')),
+    swapped_from INTEGER REFERENCES term_subjects (id),
+    -- NULL for changes made by the system, e.g. waitlist promotions
+    actor_id INTEGER REFERENCES users (id),
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    ip TEXT,
+    user_agent TEXT,
+    reason TEXT,
+    CHECK ((action = 'swap') = (swapped_from IS NOT NULL))
+  ) STRICT;
+
+CREATE INDEX IF NOT EXISTS
+  registration_events_student ON registration_events (student_id);
+
+CREATE TRIGGER IF NOT EXISTS
+  registration_events_no_update
+BEFORE UPDATE ON registration_events
+BEGIN
+  SELECT RAISE(ABORT, 'registration_events is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS
+  registration_events_no_delete
+BEFORE DELETE ON registration_events
+BEGIN
+  SELECT RAISE(ABORT, 'registration_events is append-only');
+END;
@@ -0,0 +1,104 @@
+-- depends on `grading_scales` and the marks columns of `completed`, see
+-- 20231124120000_grading_scales.sql
+
+-- the credit hours that make up a level, one policy per bylaw
+CREATE TABLE IF NOT EXISTS
+  level_policies (
+    by_law INTEGER PRIMARY KEY,
+    credits_per_level INTEGER NOT NULL CHECK (credits_per_level > 0)
+  ) STRICT;
+
+-- the default policy for the existing bylaws, two terms of the regular load
+INSERT OR IGNORE INTO level_policies (by_law, credits_per_level)
+SELECT DISTINCT by_law, 36
+FROM programs;
+
+-- the level of every student, derived from their earned credit hours. A
+-- subject counts once any attempt at it passes, graded the same way as the
+-- transcript. Students whose bylaw has no level policy are left out
+CREATE VIEW IF NOT EXISTS
+  student_levels AS
+SELECT u.profile_id,
+       1 + (
+         SELECT COALESCE(SUM(s.credit), 0)
+         FROM subjects AS s
+         WHERE s.id IN (
+           SELECT c.subject_id
+           FROM completed AS c
+           WHERE c.student_id = u.id
+             AND COALESCE(
+               (
+                 SELECT g.passing
+                 FROM grading_scales AS g
+                 WHERE g.by_law = c.by_law
+                   AND IIF(
+                     c.coursework IS NULL
+                     OR c.midterm IS NULL
+                     OR c.final_exam IS NULL,
+                     g.points <= c.gpa,
+                     g.min_mark <= c.coursework + c.midterm + c.final_exam
+                   )
+                 ORDER BY g.min_mark DESC
+                 LIMIT 1
+               ),
+               -- bylaws without a scale, only attempts graded before marks
+               -- were stored can pass
+               c.gpa > 0 AND c.coursework IS NULL
+             )
+         )
+       ) / lp.credits_per_level AS level
+FROM users AS u
+INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+INNER JOIN programs AS p ON p.id = sp.program_id
+INNER JOIN level_policies AS lp ON lp.by_law = p.by_law;
+
+-- used to stagger registration, see `registration_start_times`
+ALTER TABLE student_profile ADD COLUMN level INTEGER NOT NULL DEFAULT 1;
+
+UPDATE student_profile
+SET level = COALESCE(
+  (SELECT level FROM student_levels WHERE profile_id = student_profile.id),
+  1
+);
+
+-- keeps `level` up to date with the student's grades
+CREATE TRIGGER IF NOT EXISTS
+  completed_level_insert
+  AFTER INSERT ON completed
+  FOR EACH ROW
+    BEGIN
+      UPDATE student_profile
+      SET level = COALESCE(
+        (SELECT level FROM student_levels WHERE profile_id = student_profile.id),
+        1
+      )
+      WHERE id = (SELECT profile_id FROM users WHERE id = NEW.student_id);
+    END;
+
+CREATE TRIGGER IF NOT EXISTS
+  completed_level_update
+  AFTER UPDATE ON completed
+  FOR EACH ROW
+    BEGIN
+      UPDATE student_profile
+      SET level = COALESCE(
+        (SELECT level FROM student_levels WHERE profile_id = student_profile.id),
+        1
+      )
+      WHERE id IN (
+        SELECT profile_id FROM users WHERE id IN (OLD.student_id, NEW.student_id)
+      );
+    END;
+
+CREATE TRIGGER IF NOT EXISTS
+  completed_level_delete
+  AFTER DELETE ON completed
+  FOR EACH ROW
+    BEGIN
+      UPDATE student_profile
+      SET level = COALESCE(
+        (SELECT level FROM student_levels WHERE profile_id = student_profile.id),
+        1
+      )
+      WHERE id = (SELECT profile_id FROM users WHERE id = OLD.student_id);
+    END;
@@ -2,13 +2,14 @@ use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
 
+use crate::audit::DegreeAuditPage;
 use crate::components::navbar::{Navbar, SideNavbar};
 
-use crate::grades::GradesPage;
+use crate::grades::{GradesPage, TranscriptVerifyPage};
 use crate::login::*;
 use crate::profile::ProfilePage;
 use crate::registration::RegistrationPage;
-use crate::timetable::TimetablePage;
+use crate::timetable::{TimetablePage, TimetablePrintPage};
 
 pub type UserResource =
     Resource<(usize, usize), Result<Option<User>, ServerFnError>>;
@@ -37,6 +38,8 @@ pub fn App() -> impl IntoView {
                     path="login"
                     view=move || view! { <LoginPage action=login user/> }
                 />
+                <Route path="timetable/print" view=TimetablePrintPage/>
+                <Route path="transcript/verify" view=TranscriptVerifyPage/>
                 <Route path="/" view=move || view!(<MainWrapper user/>)>
                     <Route path="" view=ProfilePage/>
                     <Route path="email" view=move || view! { "email" }/>
@@ -44,6 +47,7 @@ pub fn App() -> impl IntoView {
                     <Route path="timetable" view=TimetablePage/>
                     <Route path="financial" view=move || view! { "financial" }/>
                     <Route path="grades" view=GradesPage/>
+                    <Route path="audit" view=DegreeAuditPage/>
                     <Route path="profile" view=move || view! { "profile" }/>
                     <Route path="/*any" view=NotFound/>
                 </Route>
@@ -0,0 +1,455 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::suserr::SusErr;
+
+#[cfg(feature = "ssr")]
+use {crate::login::UserId, std::collections::HashSet};
+
+/// Credit hours per term assumed for students without any earned credits
+#[cfg(feature = "ssr")]
+const REGULAR_LOAD: u32 = 18;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "ssr",
+    derive(sqlx::Type),
+    sqlx(rename_all = "snake_case")
+)]
+pub enum GroupKind {
+    /// Every subject must be passed
+    Required,
+    /// A pool of subjects, `min_credits` of which must be passed
+    Elective,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SubjectStatus {
+    Passed,
+    /// Registered in the current term
+    InProgress,
+    Remaining,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct AuditSubject {
+    pub subject_id: i64,
+    pub code: String,
+    pub name: String,
+    pub credit: u32,
+    pub status: SubjectStatus,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct GroupAudit {
+    pub name: String,
+    pub kind: GroupKind,
+    pub level: Option<u32>,
+    /// The credits of every subject for required groups,
+    /// the pool's minimum for electives
+    pub required_credits: u32,
+    pub earned_credits: u32,
+    pub in_progress_credits: u32,
+    pub complete: bool,
+    pub subjects: Vec<AuditSubject>,
+}
+
+impl GroupAudit {
+    pub fn remaining_credits(&self) -> u32 {
+        self.required_credits.saturating_sub(self.earned_credits)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct DegreeAudit {
+    pub program: String,
+    pub by_law: i64,
+    pub total_credits: u32,
+    pub earned_credits: u32,
+    pub in_progress_credits: u32,
+    pub remaining_credits: u32,
+    pub groups: Vec<GroupAudit>,
+    /// Terms left including the current one, 0 if all requirements are met
+    pub projected_terms: u32,
+    /// The term the student is projected to graduate in,
+    /// `None` if it isn't on the academic calendar yet
+    pub projected_term: Option<String>,
+}
+
+/// A subject of a curriculum group
+#[cfg(feature = "ssr")]
+pub(crate) struct CurriculumRow {
+    pub group_id: i64,
+    pub group_name: String,
+    pub kind: GroupKind,
+    pub level: Option<u32>,
+    pub min_credits: u32,
+    pub subject_id: i64,
+    pub code: String,
+    pub name: String,
+    pub credit: u32,
+}
+
+/// Groups the curriculum rows, which must be sorted by group,
+/// and checks each group against the student's subjects
+#[cfg(feature = "ssr")]
+pub(crate) fn audit_groups(
+    rows: Vec<CurriculumRow>,
+    passed: &HashSet<i64>,
+    in_progress: &HashSet<i64>,
+) -> Vec<GroupAudit> {
+    let mut groups: Vec<(i64, GroupAudit)> = Vec::new();
+    for row in rows {
+        if groups.last().is_none_or(|(id, _)| *id != row.group_id) {
+            let group = GroupAudit {
+                name: row.group_name,
+                kind: row.kind,
+                level: row.level,
+                required_credits: match row.kind {
+                    GroupKind::Required => 0,
+                    GroupKind::Elective => row.min_credits,
+                },
+                earned_credits: 0,
+                in_progress_credits: 0,
+                complete: false,
+                subjects: Vec::new(),
+            };
+            groups.push((row.group_id, group));
+        }
+        let (_, group) = groups.last_mut().expect("pushed above");
+
+        let status = if passed.contains(&row.subject_id) {
+            group.earned_credits += row.credit;
+            SubjectStatus::Passed
+        } else if in_progress.contains(&row.subject_id) {
+            group.in_progress_credits += row.credit;
+            SubjectStatus::InProgress
+        } else {
+            SubjectStatus::Remaining
+        };
+        if group.kind == GroupKind::Required {
+            group.required_credits += row.credit;
+        }
+        group.subjects.push(AuditSubject {
+            subject_id: row.subject_id,
+            code: row.code,
+            name: row.name,
+            credit: row.credit,
+            status,
+        });
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, mut g)| {
+            g.complete = match g.kind {
+                GroupKind::Required => {
+                    g.subjects.iter().all(|s| s.status == SubjectStatus::Passed)
+                }
+                GroupKind::Elective => g.earned_credits >= g.required_credits,
+            };
+            g
+        })
+        .collect()
+}
+
+/// Terms needed to earn `remaining` credits, counting the current term if
+/// `in_progress` credits are being taken. Students that haven't earned any
+/// credits yet are assumed to take the regular load
+#[cfg(feature = "ssr")]
+pub(crate) fn projected_terms(
+    remaining: u32,
+    in_progress: u32,
+    credits_per_term: u32,
+) -> u32 {
+    if remaining == 0 {
+        return 0;
+    }
+    let after_current = remaining.saturating_sub(in_progress);
+    let current = (in_progress > 0) as u32;
+    let credits_per_term = match credits_per_term {
+        0 => REGULAR_LOAD,
+        c => c,
+    };
+    current + after_current.div_ceil(credits_per_term)
+}
+
+/// Returns the degree audit of the student,
+/// `None` if their program has no curriculum under its bylaw
+#[cfg(feature = "ssr")]
+pub(crate) async fn degree_audit(
+    pool: &sqlx::SqlitePool,
+    student_id: UserId,
+) -> sqlx::Result<Option<DegreeAudit>> {
+    let curriculum = sqlx::query!(
+        r#"
+            SELECT c.id,
+                   c.total_credits AS "total_credits: u32",
+                   p.name AS program,
+                   p.by_law
+            FROM users AS u
+            INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+            INNER JOIN programs AS p ON p.id = sp.program_id
+            INNER JOIN curricula AS c
+                ON c.program_id = p.id AND c.by_law = p.by_law
+            WHERE u.id = ?
+        "#,
+        student_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(curriculum) = curriculum else {
+        return Ok(None);
+    };
+
+    let rows = sqlx::query_as!(
+        CurriculumRow,
+        r#"
+            SELECT g.id AS group_id,
+                   g.name AS group_name,
+                   g.kind AS "kind: GroupKind",
+                   g.level AS "level: u32",
+                   g.min_credits AS "min_credits: u32",
+                   s.id AS subject_id,
+                   s.code,
+                   s.name,
+                   s.credit AS "credit: u32"
+            FROM curriculum_groups AS g
+            INNER JOIN curriculum_subjects AS cs ON cs.group_id = g.id
+            INNER JOIN subjects AS s ON s.id = cs.subject_id
+            WHERE g.curriculum_id = ?
+            ORDER BY g.level IS NULL, g.level, g.id, s.code
+        "#,
+        curriculum.id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let grades = crate::grades::student_grades(pool, student_id).await?;
+    let passed: HashSet<i64> = grades
+        .terms
+        .iter()
+        .flat_map(|t| &t.subjects)
+        .filter(|s| s.counted && s.passed)
+        .map(|s| s.subject_id)
+        .collect();
+
+    let enrolled = sqlx::query!(
+        r#"
+            SELECT DISTINCT s.id, s.credit AS "credit: u32"
+            FROM term_subscribers AS tsub
+            INNER JOIN term_subjects AS ts ON ts.id = tsub.term_subject_id
+            INNER JOIN subjects AS s ON s.id = ts.subject_id
+            WHERE tsub.student_id = ?
+        "#,
+        student_id
+    )
+    .fetch_all(pool)
+    .await?;
+    let in_progress: HashSet<i64> = enrolled.iter().map(|r| r.id).collect();
+    let in_progress_credits = enrolled.iter().map(|r| r.credit).sum();
+
+    let groups = audit_groups(rows, &passed, &in_progress);
+    let remaining_credits = curriculum
+        .total_credits
+        .saturating_sub(grades.earned_credits)
+        .max(groups.iter().map(GroupAudit::remaining_credits).sum());
+
+    let credits_per_term = match grades.terms.len() as u32 {
+        0 => REGULAR_LOAD,
+        terms => grades.earned_credits / terms,
+    };
+    let projected_terms = projected_terms(
+        remaining_credits,
+        in_progress_credits,
+        credits_per_term,
+    );
+
+    // the running term is the first one
+    let today = crate::utils::today().to_string();
+    let projected_term = match projected_terms.checked_sub(1) {
+        Some(offset) => {
+            let offset = offset as i64;
+            sqlx::query_scalar!(
+                r#"
+                    SELECT abs
+                    FROM academic_terms
+                    WHERE ends_on >= ?
+                    ORDER BY starts_on
+                    LIMIT 1 OFFSET ?
+                "#,
+                today,
+                offset
+            )
+            .fetch_optional(pool)
+            .await?
+        }
+        None => None,
+    };
+
+    Ok(Some(DegreeAudit {
+        program: curriculum.program,
+        by_law: curriculum.by_law,
+        total_credits: curriculum.total_credits,
+        earned_credits: grades.earned_credits,
+        in_progress_credits,
+        remaining_credits,
+        groups,
+        projected_terms,
+        projected_term,
+    }))
+}
+
+#[server(encoding = "GetJson")]
+pub async fn get_degree_audit() -> Result<Option<DegreeAudit>, ServerFnError> {
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Err(ServerFnError::ServerError("Auth Error".into()));
+    };
+
+    Ok(degree_audit(&pool, student_id).await?)
+}
+
+#[component]
+pub fn DegreeAuditPage() -> impl IntoView {
+    let audit = create_resource(|| (), |_| get_degree_audit());
+
+    view! {
+        <h1 class="text-4xl mb-7">"Degree Audit"</h1>
+        <SusErr resource=audit let:audit>
+            {match audit {
+                Some(audit) => view! { <Audit audit/> }.into_view(),
+                None => view! {
+                    <p>"No curriculum is defined for your program yet"</p>
+                }.into_view(),
+            }}
+        </SusErr>
+    }
+}
+
+#[component]
+fn audit<'a>(audit: &'a DegreeAudit) -> impl IntoView {
+    let projection = match (audit.projected_terms, &audit.projected_term) {
+        (0, _) => "All graduation requirements are met".to_owned(),
+        (_, Some(term)) => format!("Projected to graduate in {term}"),
+        (n, None) => format!("Projected to graduate in {n} terms"),
+    };
+    let percent = match audit.total_credits {
+        0 => 100,
+        total => (audit.earned_credits * 100 / total).min(100),
+    };
+
+    view! {
+        <div class="flex flex-wrap gap-x-6 pb-2">
+            <span>"Program: " <b>{&audit.program}</b></span>
+            <span>"Bylaw: " <b>{audit.by_law}</b></span>
+            <span>"Earned: " <b>{format!("{} / {}", audit.earned_credits, audit.total_credits)}</b></span>
+            <span>"In progress: " <b>{audit.in_progress_credits}</b></span>
+            <span>"Remaining: " <b>{audit.remaining_credits}</b></span>
+        </div>
+        <div class="h-2 w-full bg-secondary rounded">
+            <div class="h-2 bg-green-500 rounded" style=format!("width: {percent}%")/>
+        </div>
+        <p class="py-2">{projection}</p>
+        <div class="flex flex-col gap-4">
+            {audit.groups.iter().map(|group| view! { <GroupCard group/> }).collect_view()}
+        </div>
+    }
+}
+
+#[component]
+fn group_card<'a>(group: &'a GroupAudit) -> impl IntoView {
+    let kind = match group.kind {
+        GroupKind::Required => "Required".to_owned(),
+        GroupKind::Elective => {
+            format!("Elective, {} credit hours", group.required_credits)
+        }
+    };
+    let level = group.level.map(|l| format!(" - Level {l}"));
+
+    view! {
+        <section class="bg-secondary rounded shadow p-4">
+            <h2 class="text-2xl">
+                {&group.name}
+                <span class="ml-2 text-base font-thin">{kind}{level}</span>
+            </h2>
+            <p class="text-sm pb-2" class:text-green-600=group.complete>
+                {match group.complete {
+                    true => "Complete".to_owned(),
+                    false => format!("{} credit hours remaining", group.remaining_credits()),
+                }}
+            </p>
+            <ul class="grid md:grid-cols-2 gap-x-4">
+                {group.subjects.iter().map(|s| {
+                    let (status, class) = match s.status {
+                        SubjectStatus::Passed => ("Passed", "text-green-600"),
+                        SubjectStatus::InProgress => ("In progress", "text-yellow-600"),
+                        SubjectStatus::Remaining => ("Remaining", "opacity-60"),
+                    };
+                    view! {
+                        <li class="flex justify-between gap-2">
+                            <span>{format!("{} {} ({})", s.code, s.name, s.credit)}</span>
+                            <span class=class>{status}</span>
+                        </li>
+                    }
+                }).collect_view()}
+            </ul>
+        </section>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod test {
+    use super::*;
+
+    fn row(group_id: i64, kind: GroupKind, subject_id: i64) -> CurriculumRow {
+        CurriculumRow {
+            group_id,
+            group_name: format!("Group {group_id}"),
+            kind,
+            level: None,
+            min_credits: 6,
+            subject_id,
+            code: format!("S{subject_id}"),
+            name: format!("Subject {subject_id}"),
+            credit: 3,
+        }
+    }
+
+    #[test]
+    fn groups() {
+        use GroupKind::*;
+        let rows = vec![
+            row(1, Required, 1),
+            row(1, Required, 2),
+            row(2, Elective, 3),
+            row(2, Elective, 4),
+            row(2, Elective, 5),
+        ];
+        let passed = HashSet::from([1, 3, 4]);
+        let in_progress = HashSet::from([2]);
+
+        let groups = audit_groups(rows, &passed, &in_progress);
+        assert_eq!(groups.len(), 2);
+        assert!(!groups[0].complete);
+        assert_eq!(groups[0].required_credits, 6);
+        assert_eq!(groups[0].remaining_credits(), 3);
+        assert_eq!(groups[0].in_progress_credits, 3);
+        assert!(groups[1].complete);
+        assert_eq!(groups[1].remaining_credits(), 0);
+    }
+
+    #[test]
+    fn projection() {
+        assert_eq!(projected_terms(0, 0, 18), 0);
+        assert_eq!(projected_terms(18, 18, 18), 1);
+        assert_eq!(projected_terms(40, 18, 18), 3);
+        assert_eq!(projected_terms(40, 0, 18), 3);
+        assert_eq!(projected_terms(10, 0, 0), 1);
+        assert_eq!(projected_terms(40, 3, 0), 4);
+    }
+}
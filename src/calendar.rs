@@ -0,0 +1,376 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use crate::class::{Class, WeekParity};
+use crate::components::suserr::TransErr;
+use crate::timetable::TimetableList;
+
+#[cfg(feature = "ssr")]
+use {crate::login::UserId, chrono::NaiveDate};
+
+/// All dates are ISO 8601, i.e. "YYYY-MM-DD"
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct AcademicTerm {
+    pub id: i64,
+    /// The term name, e.g "Fall 2023"
+    pub abs: String,
+    pub starts_on: String,
+    pub ends_on: String,
+    /// The first day of the first week
+    pub week_one: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "ssr",
+    derive(sqlx::Type),
+    sqlx(rename_all = "snake_case")
+)]
+pub enum EventKind {
+    Holiday,
+    Exams,
+}
+
+/// A holiday or exam period, no classes are held during either
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct CalendarEvent {
+    pub kind: EventKind,
+    pub name: String,
+    /// inclusive range
+    pub starts_on: String,
+    pub ends_on: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct TermCalendar {
+    pub term: AcademicTerm,
+    pub events: Vec<CalendarEvent>,
+}
+
+/// What a student has on a specific date
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Day {
+    pub date: String,
+    pub term: Option<AcademicTerm>,
+    /// The week of the term, `None` outside of a term or before its first week
+    pub week: Option<u32>,
+    pub events: Vec<CalendarEvent>,
+    pub classes: Vec<Class>,
+}
+
+/// Returns the term running on `date`
+#[cfg(feature = "ssr")]
+pub(crate) async fn term_on(
+    pool: &sqlx::SqlitePool,
+    date: NaiveDate,
+) -> sqlx::Result<Option<AcademicTerm>> {
+    let date = date.to_string();
+    sqlx::query_as!(
+        AcademicTerm,
+        r#"
+            SELECT id, abs, starts_on, ends_on, week_one
+            FROM academic_terms
+            WHERE ? BETWEEN starts_on AND ends_on
+        "#,
+        date
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns the week of `term` that `date` falls in, starting from 1
+#[cfg(feature = "ssr")]
+pub(crate) fn week_of(term: &AcademicTerm, date: NaiveDate) -> Option<u32> {
+    // guaranteed to be a valid date by the db
+    let week_one =
+        NaiveDate::parse_from_str(&term.week_one, "%Y-%m-%d").ok()?;
+    let days = (date - week_one).num_days();
+    (days >= 0).then_some(days as u32 / 7 + 1)
+}
+
+/// Returns the running term, or the next one if none is running
+#[cfg(feature = "ssr")]
+pub(crate) async fn current_or_next_term(
+    pool: &sqlx::SqlitePool,
+) -> sqlx::Result<Option<AcademicTerm>> {
+    let today = crate::utils::today().to_string();
+    sqlx::query_as!(
+        AcademicTerm,
+        r#"
+            SELECT id, abs, starts_on, ends_on, week_one
+            FROM academic_terms
+            WHERE ends_on >= ?
+            ORDER BY starts_on
+            LIMIT 1
+        "#,
+        today
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) async fn term_events(
+    pool: &sqlx::SqlitePool,
+    term_id: i64,
+) -> sqlx::Result<Vec<CalendarEvent>> {
+    sqlx::query_as!(
+        CalendarEvent,
+        r#"
+            SELECT kind AS "kind: EventKind", name, starts_on, ends_on
+            FROM calendar_events
+            WHERE term_id = ?
+            ORDER BY starts_on
+        "#,
+        term_id
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Returns the student's classes on `date`, taking week parity, holidays
+/// and exam periods into account
+#[cfg(feature = "ssr")]
+pub(crate) async fn classes_on(
+    pool: &sqlx::SqlitePool,
+    student_id: UserId,
+    date: NaiveDate,
+) -> sqlx::Result<Day> {
+    use crate::class::DayOfWeek;
+    use chrono::Datelike;
+
+    let mut day = Day {
+        date: date.to_string(),
+        term: term_on(pool, date).await?,
+        week: None,
+        events: Vec::new(),
+        classes: Vec::new(),
+    };
+    let Some(term) = &day.term else {
+        return Ok(day);
+    };
+
+    day.week = week_of(term, date);
+    day.events = term_events(pool, term.id)
+        .await?
+        .into_iter()
+        .filter(|e| e.starts_on <= day.date && day.date <= e.ends_on)
+        .collect();
+    let Some(week) = day.week.filter(|_| day.events.is_empty()) else {
+        return Ok(day);
+    };
+
+    // `DayOfWeek` starts on saturday
+    let weekday = (date.weekday().num_days_from_sunday() as usize + 1) % 7;
+    let weekday = DayOfWeek::from_repr(weekday);
+    let parity = WeekParity::of_week(week);
+    day.classes = crate::timetable::student_classes(pool, student_id)
+        .await?
+        .into_iter()
+        .filter(|c| Some(c.day) == weekday)
+        .filter(|c| c.ctype.week_parity().overlaps(&parity))
+        .collect();
+
+    Ok(day)
+}
+
+/// Returns the calendar of the term named `abs`,
+/// or the current or next term if `None`
+#[server(encoding = "GetJson")]
+pub async fn get_term_calendar(
+    abs: Option<String>,
+) -> Result<Option<TermCalendar>, ServerFnError> {
+    let pool = crate::utils::extract_pool().await;
+
+    let term = match abs {
+        Some(abs) => {
+            sqlx::query_as!(
+                AcademicTerm,
+                r#"
+                    SELECT id, abs, starts_on, ends_on, week_one
+                    FROM academic_terms
+                    WHERE abs = ?
+                "#,
+                abs
+            )
+            .fetch_optional(&pool)
+            .await?
+        }
+        None => current_or_next_term(&pool).await?,
+    };
+
+    let Some(term) = term else {
+        return Ok(None);
+    };
+    let events = term_events(&pool, term.id).await?;
+    Ok(Some(TermCalendar { term, events }))
+}
+
+/// `date` is an ISO 8601 date
+#[server(encoding = "GetJson")]
+pub async fn get_classes_on(date: String) -> Result<Day, ServerFnError> {
+    use crate::login::user_id_from_jwt;
+
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Err(ServerFnError::ServerError("Unauthorized".into()));
+    };
+
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")?;
+    Ok(classes_on(&pool, student_id, date).await?)
+}
+
+/// Shows the student's classes on a picked date
+#[component]
+pub fn DayLookup() -> impl IntoView {
+    let (date, set_date) = create_signal(None::<String>);
+    let day = create_resource(date, |date| async move {
+        match date {
+            Some(date) => get_classes_on(date).await.map(Some),
+            None => Ok(None),
+        }
+    });
+
+    let describe = |day: &Day| {
+        let events: Vec<_> = day
+            .events
+            .iter()
+            .map(|e| match e.kind {
+                EventKind::Holiday => format!("Holiday: {}", e.name),
+                EventKind::Exams => format!("Exams: {}", e.name),
+            })
+            .collect();
+        match (&day.term, day.week) {
+            (None, _) => "No term is running".to_owned(),
+            _ if !events.is_empty() => events.join(", "),
+            (Some(t), Some(w)) => {
+                format!("{} - Week {w} ({})", t.abs, WeekParity::of_week(w))
+            }
+            (Some(t), None) => format!("{} - before the first week", t.abs),
+        }
+    };
+
+    view! {
+        <div class="flex gap-2 items-center py-2">
+            <label for="day_lookup">"Classes on"</label>
+            <input
+                type="date"
+                id="day_lookup"
+                class="bg-secondary rounded px-1"
+                on:change=move |e| {
+                    let value = event_target_value(&e);
+                    set_date((!value.is_empty()).then_some(value))
+                }
+            />
+        </div>
+        <TransErr resource=day let:day>
+            {day.as_ref().map(|day| view! {
+                <p class="text-sm py-1">{describe(day)}</p>
+                <TimetableList data=day.classes.clone()/>
+            })}
+        </TransErr>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod test {
+    use super::*;
+    use sqlx::SqlitePool;
+
+    fn date(d: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap()
+    }
+
+    /// A term starting on saturday 2023-09-30, with a holiday in its fourth
+    /// week. Student 1 takes a weekly lecture and an odd weeks lab on
+    /// sundays, and isn't registered to another sunday lecture
+    async fn seeded_pool() -> SqlitePool {
+        let pool = SqlitePool::connect("sqlite::memory:").await.unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+        sqlx::query(
+            r#"
+                INSERT INTO academic_terms (id, abs, starts_on, ends_on, week_one)
+                VALUES (1, 'Fall 2023', '2023-09-30', '2024-01-20', '2023-09-30');
+                INSERT INTO calendar_events (term_id, kind, name, starts_on, ends_on)
+                VALUES (1, 'holiday', 'Armed Forces Day', '2023-10-22', '2023-10-22');
+
+                INSERT INTO users (id, username, password, email, name)
+                VALUES (1, 'student', '', '', '');
+                INSERT INTO subjects (id, name, code, level, credit)
+                VALUES (1, 'Subject', 'SUB101', 1, 3);
+                INSERT INTO professors (id, name) VALUES (1, 'Prof');
+                INSERT INTO locations (id, building, floor, room)
+                VALUES (1, 'ssp', 0, 'A1');
+                INSERT INTO classes (
+                    id, type, day_of_week, week_parity, period_start,
+                    period_end, subject_id, location_id
+                )
+                VALUES
+                    (1, 'lec', 'sunday', 'both', 0, 1, 1, 1),
+                    (2, 'lab', 'sunday', 'odd', 2, 3, 1, 1),
+                    (3, 'lec', 'sunday', 'both', 4, 5, 1, 1);
+                INSERT INTO term_subjects (
+                    id, max_seats, group_no, sec_no, subject_id, prof_id,
+                    lec_id, lab_id
+                )
+                VALUES
+                    (1, 10, 1, 1, 1, 1, 1, 2),
+                    (2, 10, 2, 1, 1, 1, 3, NULL);
+                INSERT INTO term_subscribers (student_id, term_subject_id)
+                VALUES (1, 1);
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        pool
+    }
+
+    async fn day(pool: &SqlitePool, d: &str) -> Day {
+        classes_on(pool, UserId::from(1), date(d)).await.unwrap()
+    }
+
+    async fn class_ids(pool: &SqlitePool, d: &str) -> Vec<String> {
+        let day = day(pool, d).await;
+        day.classes.iter().map(|c| c.id.to_string()).collect()
+    }
+
+    #[test]
+    fn weeks() {
+        let term = AcademicTerm {
+            id: 1,
+            abs: "Fall 2023".into(),
+            starts_on: "2023-09-23".into(),
+            ends_on: "2024-01-20".into(),
+            week_one: "2023-09-30".into(),
+        };
+        assert_eq!(week_of(&term, date("2023-09-29")), None);
+        assert_eq!(week_of(&term, date("2023-09-30")), Some(1));
+        assert_eq!(week_of(&term, date("2023-10-06")), Some(1));
+        assert_eq!(week_of(&term, date("2023-10-07")), Some(2));
+        assert_eq!(week_of(&term, date("2024-01-20")), Some(17));
+    }
+
+    #[actix_web::test]
+    async fn classes() {
+        let pool = seeded_pool().await;
+
+        // odd weeks have the lab too
+        assert_eq!(class_ids(&pool, "2023-10-01").await, ["1", "2"]);
+        assert_eq!(class_ids(&pool, "2023-10-08").await, ["1"]);
+        assert!(class_ids(&pool, "2023-10-02").await.is_empty());
+
+        let holiday = day(&pool, "2023-10-22").await;
+        assert_eq!(holiday.week, Some(4));
+        assert_eq!(holiday.events.len(), 1);
+        assert!(holiday.classes.is_empty());
+
+        let summer = day(&pool, "2023-08-01").await;
+        assert_eq!((summer.term, summer.week), (None, None));
+        assert!(summer.classes.is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use strum_macros::{Display, EnumIs, FromRepr, IntoStaticStr};
+use strum_macros::{Display, EnumIs, EnumString, FromRepr, IntoStaticStr};
 
 #[derive(Hash, Copy, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
 #[cfg_attr(
@@ -14,6 +14,35 @@ pub enum WeekParity {
     Odd,
 }
 
+impl std::fmt::Display for WeekParity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Both => write!(f, "weekly"),
+            Self::Even => write!(f, "even weeks"),
+            Self::Odd => write!(f, "odd weeks"),
+        }
+    }
+}
+
+impl WeekParity {
+    /// The parity of the `week`th week of the term, starting from 1
+    pub fn of_week(week: u32) -> Self {
+        if week % 2 == 0 {
+            Self::Even
+        } else {
+            Self::Odd
+        }
+    }
+
+    /// Returns true if both parities share at least one week
+    pub fn overlaps(&self, other: &WeekParity) -> bool {
+        !matches!(
+            (self, other),
+            (Self::Odd, Self::Even) | (Self::Even, Self::Odd)
+        )
+    }
+}
+
 #[derive(Hash, Copy, Clone, PartialEq, Eq, Deserialize, Serialize, Default)]
 #[cfg_attr(feature = "ssr", derive(sqlx::Type))]
 #[repr(i64)]
@@ -41,6 +70,16 @@ pub enum Type {
     },
 }
 
+impl Type {
+    pub fn week_parity(&self) -> WeekParity {
+        match self {
+            Type::Lecture { .. } => WeekParity::Both,
+            Type::Lab { week_parity, .. }
+            | Type::Tutorial { week_parity, .. } => *week_parity,
+        }
+    }
+}
+
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -55,7 +94,18 @@ impl std::fmt::Display for Type {
     }
 }
 
-#[derive(Hash, Clone, PartialEq, Eq, Deserialize, Serialize, Copy)]
+#[derive(
+    Hash,
+    Clone,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    Copy,
+    EnumString,
+    IntoStaticStr,
+)]
+#[strum(serialize_all = "snake_case")]
 #[cfg_attr(
     feature = "ssr",
     derive(sqlx::Type),
@@ -88,6 +138,12 @@ pub struct Location {
     room: String,
 }
 
+impl Location {
+    pub fn building(&self) -> Building {
+        self.building
+    }
+}
+
 impl std::fmt::Display for Location {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -115,6 +171,7 @@ impl std::fmt::Display for Location {
     Serialize,
     Copy,
     Display,
+    EnumString,
     FromRepr,
     IntoStaticStr,
 )]
@@ -143,6 +200,12 @@ impl DayOfWeek {
 #[cfg_attr(feature = "ssr", derive(sqlx::Type), sqlx(transparent))]
 pub struct ClassId(i64);
 
+impl std::fmt::Display for ClassId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl From<i64> for ClassId {
     fn from(id: i64) -> Self {
         Self(id)
@@ -167,6 +230,36 @@ pub struct Class {
     pub period: (usize, usize),
 }
 
+impl Class {
+    pub fn time_slot(&self) -> TimeSlot {
+        TimeSlot {
+            day: self.day,
+            period: self.period,
+            week_parity: self.ctype.week_parity(),
+        }
+    }
+}
+
+/// The weekly time occupied by a class
+#[derive(Hash, Clone, PartialEq, Eq, Deserialize, Serialize, Copy)]
+pub struct TimeSlot {
+    pub day: DayOfWeek,
+    /// inclusive range, 0-indexed
+    pub period: (usize, usize),
+    pub week_parity: WeekParity,
+}
+
+impl TimeSlot {
+    /// Returns true if both slots share at least one period on the same day
+    /// of the same week
+    pub fn overlaps(&self, other: &TimeSlot) -> bool {
+        self.day == other.day
+            && self.period.0 <= other.period.1
+            && other.period.0 <= self.period.1
+            && self.week_parity.overlaps(&other.week_parity)
+    }
+}
+
 impl std::fmt::Debug for Class {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Class")
@@ -233,3 +326,81 @@ pub mod db {
         }
     }
 }
+
+/// Builders of the classes used by tests across the crate
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::*;
+
+    /// A class in a Mechanics room, leaving out what the tests don't look at
+    pub(crate) fn class(
+        id: i64,
+        ctype: Type,
+        day: DayOfWeek,
+        period: (usize, usize),
+    ) -> Class {
+        Class {
+            id: ClassId(id),
+            ctype,
+            code: String::new(),
+            name: String::new(),
+            location: Location {
+                building: Building::Mechanics,
+                floor: 1,
+                room: "A".to_owned(),
+            },
+            day,
+            period,
+        }
+    }
+
+    /// A lecture if `week_parity` is `Both`, a lab of the first section
+    /// otherwise
+    pub(crate) fn by_parity(week_parity: WeekParity) -> Type {
+        match week_parity {
+            WeekParity::Both => Type::Lecture { prof: String::new() },
+            week_parity => Type::Lab { sec_no: Section::One, week_parity },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn slot(day: DayOfWeek, period: (usize, usize), p: WeekParity) -> TimeSlot {
+        TimeSlot { day, period, week_parity: p }
+    }
+
+    #[test]
+    fn parity_overlaps() {
+        use WeekParity::*;
+        let cases = [
+            (Both, Both, true),
+            (Both, Odd, true),
+            (Both, Even, true),
+            (Odd, Both, true),
+            (Odd, Odd, true),
+            (Odd, Even, false),
+            (Even, Both, true),
+            (Even, Odd, false),
+            (Even, Even, true),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(a.overlaps(&b), expected);
+
+            let a = slot(DayOfWeek::Sunday, (0, 1), a);
+            let b = slot(DayOfWeek::Sunday, (1, 2), b);
+            assert_eq!(a.overlaps(&b), expected);
+        }
+    }
+
+    #[test]
+    fn slot_overlaps_needs_same_day_and_period() {
+        use WeekParity::*;
+        let a = slot(DayOfWeek::Sunday, (0, 1), Odd);
+        assert!(!a.overlaps(&slot(DayOfWeek::Monday, (0, 1), Odd)));
+        assert!(!a.overlaps(&slot(DayOfWeek::Sunday, (2, 3), Odd)));
+        assert!(a.overlaps(&slot(DayOfWeek::Sunday, (1, 1), Both)));
+    }
+}
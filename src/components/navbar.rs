@@ -103,6 +103,10 @@ pub fn SideNavbar() -> impl IntoView {
                 {icon!("mdi/trophy-outline", "text-3xl")}
                 <span class=LABEL_CLASS>"Grades"</span>
             </A>
+            <A class=LINK_CLASS href="/audit">
+                {icon!("mdi/certificate-outline", "text-3xl")}
+                <span class=LABEL_CLASS>"Degree Audit"</span>
+            </A>
         </nav>
     }
 }
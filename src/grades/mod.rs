@@ -0,0 +1,518 @@
+mod scale;
+pub mod transcript;
+
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use crate::components::suserr::SusErr;
+pub use scale::{GradeLevel, GradingScale, Marks};
+pub use transcript::TranscriptVerifyPage;
+
+#[cfg(feature = "ssr")]
+use std::collections::HashMap;
+
+/// Which attempt of a repeated subject counts towards the cumulative GPA,
+/// set per program
+#[derive(
+    Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default,
+)]
+#[cfg_attr(
+    feature = "ssr",
+    derive(sqlx::Type),
+    sqlx(rename_all = "snake_case")
+)]
+pub enum RepeatPolicy {
+    /// The attempt with the highest grade, the latest one on ties
+    #[default]
+    Best,
+    Latest,
+}
+
+/// An attempt at a subject
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SubjectGrade {
+    pub subject_id: i64,
+    pub code: String,
+    pub name: String,
+    pub credit: u32,
+    /// The grade points
+    pub gpa: f64,
+    /// `None` if the bylaw has no grading scale
+    pub letter: Option<String>,
+    /// `None` for attempts graded before marks were stored
+    pub marks: Option<Marks>,
+    pub passed: bool,
+    /// false if superseded by another attempt at the same subject
+    pub counted: bool,
+    /// true if the subject was attempted in an earlier term
+    pub repeated: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TermGrades {
+    pub term_no: u32,
+    pub term_abs: String,
+    pub subjects: Vec<SubjectGrade>,
+    /// The GPA of every attempt in the term, `None` if no credits were taken
+    pub gpa: Option<f64>,
+    pub earned_credits: u32,
+    /// The cumulative GPA up to and including this term
+    pub cum_gpa: Option<f64>,
+    pub cum_credits: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Grades {
+    pub policy: RepeatPolicy,
+    /// sorted by `term_no`
+    pub terms: Vec<TermGrades>,
+    pub gpa: Option<f64>,
+    pub earned_credits: u32,
+}
+
+/// A row of `completed`
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+pub(crate) struct Attempt {
+    pub subject_id: i64,
+    pub code: String,
+    pub name: String,
+    pub credit: u32,
+    pub term_no: u32,
+    pub term_abs: String,
+    pub completed_on: String,
+    /// The stored grade points, only used if `marks` is `None` or the bylaw
+    /// has no grading scale
+    pub gpa: f64,
+    pub marks: Option<Marks>,
+    /// The bylaw the attempt was graded under
+    pub by_law: i64,
+}
+
+#[cfg(feature = "ssr")]
+impl Attempt {
+    /// Grades the attempt using the scale of its bylaw
+    fn grade(&self, scales: &HashMap<i64, GradingScale>) -> SubjectGrade {
+        let scale = scales.get(&self.by_law);
+        let (gpa, level) = match (self.marks, scale) {
+            (Some(marks), Some(scale)) => {
+                let level = scale.grade(marks.total());
+                (level.map_or(0.0, |l| l.points), level)
+            }
+            // marks can't be graded without a scale, keep the stored points
+            _ => (self.gpa, scale.and_then(|s| s.grade_of_points(self.gpa))),
+        };
+
+        SubjectGrade {
+            subject_id: self.subject_id,
+            code: self.code.clone(),
+            name: self.name.clone(),
+            credit: self.credit,
+            gpa,
+            letter: level.map(|l| l.letter.clone()),
+            marks: self.marks,
+            passed: level.map_or(gpa > 0.0, |l| l.passing),
+            counted: false,
+            repeated: false,
+        }
+    }
+}
+
+/// Credit weighted GPA of `(credit, gpa)` pairs
+#[cfg(feature = "ssr")]
+fn weighted_gpa(grades: impl Iterator<Item = (u32, f64)>) -> Option<f64> {
+    let (credits, points) = grades.fold((0, 0.0), |(c, p), (credit, gpa)| {
+        (c + credit, p + credit as f64 * gpa)
+    });
+    (credits > 0).then(|| points / credits as f64)
+}
+
+/// Groups the attempts into terms, choosing the counted attempt of repeated
+/// subjects according to `policy`
+#[cfg(feature = "ssr")]
+pub(crate) fn grades_from_attempts(
+    mut attempts: Vec<Attempt>,
+    policy: RepeatPolicy,
+    scales: &HashMap<i64, GradingScale>,
+) -> Grades {
+    attempts.sort_by(|a, b| {
+        (a.term_no, &a.completed_on).cmp(&(b.term_no, &b.completed_on))
+    });
+    let graded: Vec<_> = attempts.iter().map(|a| a.grade(scales)).collect();
+
+    // maps `subject_id` to the index of the counted attempt
+    let mut counted: HashMap<i64, usize> = HashMap::new();
+    let mut terms: Vec<TermGrades> = Vec::new();
+    for (i, a) in attempts.iter().enumerate() {
+        let repeated = counted.contains_key(&a.subject_id);
+        let prev = counted.entry(a.subject_id).or_insert(i);
+        // attempts are sorted, so `a` is the latest
+        if policy == RepeatPolicy::Latest || graded[i].gpa >= graded[*prev].gpa
+        {
+            *prev = i;
+        }
+
+        if terms.last().is_none_or(|t| t.term_no != a.term_no) {
+            terms.push(TermGrades {
+                term_no: a.term_no,
+                term_abs: a.term_abs.clone(),
+                subjects: Vec::new(),
+                gpa: None,
+                earned_credits: 0,
+                cum_gpa: None,
+                cum_credits: 0,
+            });
+        }
+        let term = terms.last_mut().expect("pushed above");
+        term.subjects
+            .push(SubjectGrade { repeated, ..graded[i].clone() });
+
+        let term_ends =
+            attempts.get(i + 1).is_none_or(|n| n.term_no != a.term_no);
+        if term_ends {
+            let subjects = &term.subjects;
+            term.gpa = weighted_gpa(subjects.iter().map(|s| (s.credit, s.gpa)));
+            term.earned_credits =
+                subjects.iter().filter(|s| s.passed).map(|s| s.credit).sum();
+            let cum = counted.values().map(|&i| &graded[i]);
+            term.cum_gpa = weighted_gpa(cum.clone().map(|s| (s.credit, s.gpa)));
+            term.cum_credits = cum.filter(|s| s.passed).map(|s| s.credit).sum();
+        }
+    }
+
+    // `subjects` are pushed in the same order as `attempts`
+    let mut counted: Vec<_> = counted.into_values().collect();
+    counted.sort_unstable();
+    let mut counted = counted.into_iter().peekable();
+    let subjects = terms.iter_mut().flat_map(|t| t.subjects.iter_mut());
+    for (i, subject) in subjects.enumerate() {
+        subject.counted = counted.next_if_eq(&i).is_some();
+    }
+
+    Grades {
+        policy,
+        gpa: terms.last().and_then(|t| t.cum_gpa),
+        earned_credits: terms.last().map_or(0, |t| t.cum_credits),
+        terms,
+    }
+}
+
+/// Returns the grades of the student, using the repeat policy of their
+/// program
+#[cfg(feature = "ssr")]
+pub(crate) async fn student_grades(
+    pool: &sqlx::SqlitePool,
+    student_id: crate::login::UserId,
+) -> sqlx::Result<Grades> {
+    let policy = sqlx::query_scalar!(
+        r#"
+            SELECT p.repeat_policy AS "policy: RepeatPolicy"
+            FROM users AS u
+            INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+            INNER JOIN programs AS p ON p.id = sp.program_id
+            WHERE u.id = ?
+        "#,
+        student_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or_default();
+
+    let attempts = sqlx::query!(
+        r#"
+            SELECT c.subject_id,
+                   s.code,
+                   s.name,
+                   s.credit AS "credit: u32",
+                   c.term_no AS "term_no: u32",
+                   c.term_abs,
+                   c.completed_on,
+                   c.gpa,
+                   c.coursework,
+                   c.midterm,
+                   c.final_exam,
+                   COALESCE(c.by_law, p.by_law) AS "by_law!: i64"
+            FROM completed AS c
+            INNER JOIN subjects AS s ON s.id = c.subject_id
+            INNER JOIN users AS u ON u.id = c.student_id
+            INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+            INNER JOIN programs AS p ON p.id = sp.program_id
+            WHERE c.student_id = ?
+        "#,
+        student_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| Attempt {
+        subject_id: r.subject_id,
+        code: r.code,
+        name: r.name,
+        credit: r.credit,
+        term_no: r.term_no,
+        term_abs: r.term_abs,
+        completed_on: r.completed_on,
+        gpa: r.gpa,
+        marks: match (r.coursework, r.midterm, r.final_exam) {
+            (Some(coursework), Some(midterm), Some(final_exam)) => {
+                Some(Marks { coursework, midterm, final_exam })
+            }
+            _ => None,
+        },
+        by_law: r.by_law,
+    })
+    .collect();
+
+    let scales = scale::grading_scales(pool).await?;
+    Ok(grades_from_attempts(attempts, policy, &scales))
+}
+
+#[server(encoding = "GetJson")]
+async fn get_std_grades() -> Result<Grades, ServerFnError> {
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Err(ServerFnError::ServerError("Auth Error".into()));
+    };
+
+    Ok(student_grades(&pool, student_id).await?)
+}
+
+fn format_gpa(gpa: Option<f64>) -> String {
+    gpa.map_or("-".to_owned(), |g| format!("{g:.2}"))
+}
+
+#[component]
+pub fn GradesPage() -> impl IntoView {
+    let grades = create_resource(|| (), |_| get_std_grades());
+
+    view! {
+        <div class="flex flex-wrap justify-between items-center mb-7">
+            <h1 class="text-4xl">"Grades"</h1>
+            <div class="flex gap-2 text-sm">
+                <a class="btn-primary-outline" href="/api/transcript.pdf" rel="external" download>
+                    "Transcript (PDF)"
+                </a>
+                <a class="btn-primary-outline" href="/api/transcript.json" rel="external" download>
+                    "Transcript (JSON)"
+                </a>
+            </div>
+        </div>
+        <SusErr resource=grades let:grades>
+            <div class="flex flex-wrap gap-x-6 pb-4">
+                <span>"Cumulative GPA: " <b>{format_gpa(grades.gpa)}</b></span>
+                <span>"Earned credit hours: " <b>{grades.earned_credits}</b></span>
+                <span class="text-sm font-thin self-center">
+                    {match grades.policy {
+                        RepeatPolicy::Best => "Repeated subjects count their best attempt",
+                        RepeatPolicy::Latest => "Repeated subjects count their latest attempt",
+                    }}
+                </span>
+            </div>
+            <div class="flex flex-col gap-4">
+                {grades.terms.iter().rev().map(|term| view! { <TermCard term/> }).collect_view()}
+            </div>
+        </SusErr>
+    }
+}
+
+#[component]
+fn TermCard<'a>(term: &'a TermGrades) -> impl IntoView {
+    view! {
+        <section class="bg-secondary rounded shadow p-4">
+            <h2 class="text-2xl pb-2">
+                {format!("Term {} - {}", term.term_no, term.term_abs)}
+            </h2>
+            <table class="w-full text-left">
+                <thead>
+                    <tr>
+                        <th>"Code"</th>
+                        <th>"Subject"</th>
+                        <th>"Credit Hours"</th>
+                        <th>"Marks"</th>
+                        <th>"Grade"</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {term.subjects.iter().map(|s| view! {
+                        <tr
+                            class:line-through=!s.counted
+                            class:opacity-60=!s.counted
+                            title=(!s.counted).then_some("Superseded by another attempt")
+                        >
+                            <td>{&s.code}</td>
+                            <td>
+                                {&s.name}
+                                {s.repeated.then_some(view! { <span class="text-sm font-thin">" (repeated)"</span> })}
+                            </td>
+                            <td>{s.credit}</td>
+                            <td title=s.marks.map(|m| format!(
+                                "Coursework: {}, Midterm: {}, Final: {}",
+                                m.coursework, m.midterm, m.final_exam
+                            ))>
+                                {s.marks.map(|m| m.total())}
+                            </td>
+                            <td class:text-red-500=!s.passed>
+                                {s.letter.as_ref().map(|l| format!("{l} "))}
+                                {format!("({:.2})", s.gpa)}
+                            </td>
+                        </tr>
+                    }).collect_view()}
+                </tbody>
+            </table>
+            <div class="flex flex-wrap gap-x-6 pt-2 text-sm">
+                <span>"Term GPA: " <b>{format_gpa(term.gpa)}</b></span>
+                <span>"Earned credit hours: " <b>{term.earned_credits}</b></span>
+                <span>"Cumulative GPA: " <b>{format_gpa(term.cum_gpa)}</b></span>
+                <span>"Cumulative credit hours: " <b>{term.cum_credits}</b></span>
+            </div>
+        </section>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod test {
+    use super::*;
+
+    fn attempt(subject_id: i64, term_no: u32, gpa: f64) -> Attempt {
+        Attempt {
+            subject_id,
+            code: format!("S{subject_id}"),
+            name: format!("Subject {subject_id}"),
+            credit: 3,
+            term_no,
+            term_abs: format!("Term {term_no}"),
+            completed_on: format!("2023-0{term_no}-01"),
+            gpa,
+            marks: None,
+            by_law: 1,
+        }
+    }
+
+    fn scale() -> HashMap<i64, GradingScale> {
+        let level = |letter: &str, min_mark, points, passing| GradeLevel {
+            letter: letter.to_owned(),
+            min_mark,
+            points,
+            passing,
+        };
+        let levels = vec![
+            level("A", 90.0, 4.0, true),
+            level("B", 75.0, 3.0, true),
+            level("D", 50.0, 1.0, true),
+            level("F", 0.0, 0.0, false),
+        ];
+        HashMap::from([(1, GradingScale { by_law: 1, levels })])
+    }
+
+    fn counted(grades: &Grades) -> Vec<(u32, i64)> {
+        grades
+            .terms
+            .iter()
+            .flat_map(|t| t.subjects.iter().map(|s| (t.term_no, s)))
+            .filter(|(_, s)| s.counted)
+            .map(|(t, s)| (t, s.subject_id))
+            .collect()
+    }
+
+    #[test]
+    fn repeated_subjects() {
+        let attempts = vec![
+            attempt(2, 2, 2.0),
+            attempt(1, 1, 0.0),
+            attempt(2, 1, 3.0),
+            attempt(1, 2, 4.0),
+        ];
+
+        let scales = HashMap::new();
+        let best =
+            grades_from_attempts(attempts.clone(), RepeatPolicy::Best, &scales);
+        assert_eq!(counted(&best), vec![(1, 2), (2, 1)]);
+        assert_eq!(best.terms[0].gpa, Some(1.5));
+        assert_eq!(best.terms[0].cum_credits, 3);
+        assert_eq!(best.terms[1].gpa, Some(3.0));
+        assert_eq!(best.gpa, Some(3.5));
+        assert_eq!(best.earned_credits, 6);
+        assert!(best.terms[1].subjects.iter().all(|s| s.repeated));
+
+        let latest =
+            grades_from_attempts(attempts, RepeatPolicy::Latest, &scales);
+        assert_eq!(counted(&latest), vec![(2, 2), (2, 1)]);
+        assert_eq!(latest.gpa, Some(3.0));
+        assert_eq!(latest.earned_credits, 6);
+    }
+
+    #[test]
+    fn grading_scale() {
+        let marks = |coursework, midterm, final_exam| {
+            Some(Marks { coursework, midterm, final_exam })
+        };
+        let attempts = vec![
+            Attempt { marks: marks(20.0, 20.0, 50.0), ..attempt(1, 1, 0.0) },
+            Attempt { marks: marks(10.0, 10.0, 20.0), ..attempt(2, 1, 4.0) },
+            // graded before marks were stored
+            attempt(3, 1, 3.0),
+        ];
+
+        let grades =
+            grades_from_attempts(attempts, RepeatPolicy::Best, &scale());
+        let grades: Vec<_> = grades.terms[0]
+            .subjects
+            .iter()
+            .map(|s| (s.letter.as_deref(), s.gpa, s.passed))
+            .collect();
+        assert_eq!(
+            grades,
+            vec![
+                (Some("A"), 4.0, true),
+                (Some("F"), 0.0, false),
+                (Some("B"), 3.0, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn marks_without_scale() {
+        let marks = Marks { coursework: 20.0, midterm: 20.0, final_exam: 50.0 };
+        let attempts =
+            vec![Attempt { marks: Some(marks), ..attempt(1, 1, 3.0) }];
+
+        let grades =
+            grades_from_attempts(attempts, RepeatPolicy::Best, &HashMap::new());
+        let subject = &grades.terms[0].subjects[0];
+        assert_eq!(subject.letter, None);
+        assert_eq!(subject.gpa, 3.0);
+        assert!(subject.passed);
+        assert_eq!(grades.gpa, Some(3.0));
+    }
+
+    #[test]
+    fn legacy_points() {
+        let level = |letter: &str, min_mark, points| GradeLevel {
+            letter: letter.to_owned(),
+            min_mark,
+            points,
+            passing: true,
+        };
+        let mut scale = GradingScale {
+            by_law: 1,
+            levels: vec![
+                level("A+", 97.0, 4.0),
+                level("A", 93.0, 4.0),
+                level("A-", 89.0, 3.7),
+            ],
+        };
+        let letter = |scale: &GradingScale, points| {
+            scale.grade_of_points(points).map(|l| l.letter.clone())
+        };
+
+        assert_eq!(letter(&scale, 4.0).as_deref(), Some("A"));
+        assert_eq!(letter(&scale, 3.9).as_deref(), Some("A-"));
+        assert_eq!(letter(&scale, 3.0), None);
+        scale.levels.reverse();
+        assert_eq!(letter(&scale, 4.0).as_deref(), Some("A"));
+    }
+}
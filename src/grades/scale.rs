@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GradeLevel {
+    pub letter: String,
+    /// inclusive lower bound of the total mark, out of 100
+    pub min_mark: f64,
+    pub points: f64,
+    pub passing: bool,
+}
+
+/// The grading scale of a bylaw
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct GradingScale {
+    pub by_law: i64,
+    /// sorted by `min_mark`, highest first
+    pub levels: Vec<GradeLevel>,
+}
+
+impl GradingScale {
+    /// Returns the grade of a total mark out of 100
+    pub fn grade(&self, mark: f64) -> Option<&GradeLevel> {
+        self.levels.iter().find(|l| mark >= l.min_mark)
+    }
+
+    /// Returns the highest grade worth at most `points`,
+    /// for attempts graded before marks were stored. Of grades worth the
+    /// same, e.g. A+ and A, the one with the lowest `min_mark` is returned
+    pub fn grade_of_points(&self, points: f64) -> Option<&GradeLevel> {
+        self.levels
+            .iter()
+            .filter(|l| points >= l.points)
+            .max_by(|a, b| {
+                a.points
+                    .total_cmp(&b.points)
+                    .then(b.min_mark.total_cmp(&a.min_mark))
+            })
+    }
+}
+
+/// The component marks of an attempt, they sum up to the total out of 100
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Marks {
+    pub coursework: f64,
+    pub midterm: f64,
+    pub final_exam: f64,
+}
+
+impl Marks {
+    pub fn total(&self) -> f64 {
+        self.coursework + self.midterm + self.final_exam
+    }
+}
+
+/// Returns the grading scales keyed by bylaw
+#[cfg(feature = "ssr")]
+pub(crate) async fn grading_scales(
+    pool: &sqlx::SqlitePool,
+) -> sqlx::Result<std::collections::HashMap<i64, GradingScale>> {
+    let rows = sqlx::query!(
+        r#"
+            SELECT by_law,
+                   letter,
+                   min_mark,
+                   points,
+                   passing AS "passing: bool"
+            FROM grading_scales
+            ORDER BY by_law, min_mark DESC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut scales = std::collections::HashMap::new();
+    for r in rows {
+        scales
+            .entry(r.by_law)
+            .or_insert_with(|| GradingScale {
+                by_law: r.by_law,
+                levels: Vec::new(),
+            })
+            .levels
+            .push(GradeLevel {
+                letter: r.letter,
+                min_mark: r.min_mark,
+                points: r.points,
+                passing: r.passing,
+            });
+    }
+    Ok(scales)
+}
@@ -0,0 +1,472 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::{format_gpa, Grades, TermCard};
+use crate::components::suserr::TransErr;
+
+#[cfg(feature = "ssr")]
+use crate::login::UserId;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct TranscriptStudent {
+    /// The university id of the student
+    pub student_no: String,
+    pub name: String,
+    pub program: String,
+    pub program_code: String,
+    pub by_law: i64,
+}
+
+/// An unofficial transcript
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Transcript {
+    pub student: TranscriptStudent,
+    /// RFC 3339 timestamp
+    pub issued_at: String,
+    pub grades: Grades,
+    /// HMAC of the other fields, recipients can check it on the verification
+    /// page
+    pub verification: String,
+}
+
+/// The HMAC key of transcripts, kept apart from the session key so either
+/// can be rotated on its own
+#[cfg(feature = "ssr")]
+fn transcript_key() -> Vec<u8> {
+    std::env::var("TRANSCRIPT_KEY")
+        .expect("Expected TRANSCRIPT_KEY")
+        .into_bytes()
+}
+
+#[cfg(feature = "ssr")]
+impl Transcript {
+    /// The signed content, i.e. everything but `verification`
+    fn message(&self) -> Vec<u8> {
+        serde_json::to_vec(&(&self.student, &self.issued_at, &self.grades))
+            .expect("serializing to a vec can't fail")
+    }
+
+    fn sign(&mut self, key: &[u8]) -> jsonwebtoken::errors::Result<()> {
+        use jsonwebtoken::{crypto, Algorithm, EncodingKey};
+
+        let key = EncodingKey::from_secret(key);
+        self.verification =
+            crypto::sign(&self.message(), &key, Algorithm::HS256)?;
+        Ok(())
+    }
+
+    fn is_authentic(&self, key: &[u8]) -> bool {
+        use jsonwebtoken::{crypto, Algorithm, DecodingKey};
+
+        let key = DecodingKey::from_secret(key);
+        crypto::verify(
+            &self.verification,
+            &self.message(),
+            &key,
+            Algorithm::HS256,
+        )
+        .unwrap_or(false)
+    }
+}
+
+/// Signs and stores a transcript of the student's current grades, or returns
+/// the last one issued if the grades haven't changed since.
+/// `None` if the student has no profile
+#[cfg(feature = "ssr")]
+pub(crate) async fn issue_transcript(
+    pool: &sqlx::SqlitePool,
+    student_id: UserId,
+) -> Result<Option<Transcript>, ServerFnError> {
+    let student = sqlx::query_as!(
+        TranscriptStudent,
+        r#"
+            SELECT u.username AS student_no,
+                   sp.name_en AS name,
+                   p.name AS program,
+                   p.code AS program_code,
+                   p.by_law
+            FROM users AS u
+            INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+            INNER JOIN programs AS p ON p.id = sp.program_id
+            WHERE u.id = ?
+        "#,
+        student_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(student) = student else {
+        return Ok(None);
+    };
+
+    let grades = super::student_grades(pool, student_id).await?;
+
+    // a transcript of the same grades was already issued, reissuing it would
+    // only add a row differing in `issued_at`
+    let last = sqlx::query_scalar!(
+        r#"
+            SELECT body
+            FROM issued_transcripts
+            WHERE student_id = ?
+            ORDER BY issued_at DESC
+            LIMIT 1
+        "#,
+        student_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    if let Some(last) = last {
+        let last = serde_json::from_str::<Transcript>(&last)?;
+        if last.student == student && last.grades == grades {
+            return Ok(Some(last));
+        }
+    }
+
+    let mut transcript = Transcript {
+        student,
+        issued_at: chrono::Utc::now().to_rfc3339(),
+        grades,
+        verification: String::new(),
+    };
+    transcript.sign(&transcript_key())?;
+
+    let body = serde_json::to_string(&transcript)?;
+    sqlx::query!(
+        r#"
+            INSERT INTO issued_transcripts
+                (verification, student_id, issued_at, body)
+            VALUES (?, ?, ?, ?)
+        "#,
+        transcript.verification,
+        student_id,
+        transcript.issued_at,
+        body
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(Some(transcript))
+}
+
+/// Returns the transcript issued with the verification code,
+/// `None` if there's none or it was tampered with
+#[server(encoding = "GetJson")]
+pub async fn verify_transcript(
+    verification: String,
+) -> Result<Option<Transcript>, ServerFnError> {
+    let pool = crate::utils::extract_pool().await;
+
+    let body = sqlx::query_scalar!(
+        "SELECT body FROM issued_transcripts WHERE verification = ?",
+        verification
+    )
+    .fetch_optional(&pool)
+    .await?;
+
+    let transcript = match body {
+        Some(body) => serde_json::from_str::<Transcript>(&body)?,
+        None => return Ok(None),
+    };
+    // the code proves the transcript was issued, the signature proves its
+    // stored body wasn't edited since
+    Ok(transcript
+        .is_authentic(&transcript_key())
+        .then_some(transcript))
+}
+
+#[cfg(feature = "ssr")]
+mod export {
+    use actix_web::{http::header, web, HttpRequest, HttpResponse};
+    use printpdf::{
+        BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfLayerReference,
+    };
+
+    use super::*;
+
+    const PAGE_WIDTH: f32 = 210.0;
+    const PAGE_HEIGHT: f32 = 297.0;
+    const MARGIN: f32 = 15.0;
+    const LINE_HEIGHT: f32 = 5.0;
+
+    /// Writes lines top to bottom, adding pages as needed
+    struct Writer<'a> {
+        doc: &'a printpdf::PdfDocumentReference,
+        layer: PdfLayerReference,
+        regular: IndirectFontRef,
+        bold: IndirectFontRef,
+        y: f32,
+    }
+
+    impl Writer<'_> {
+        /// Writes `columns` on one line, each at its x offset in mm
+        fn line(&mut self, columns: &[(f32, &str)], bold: bool) {
+            if self.y < MARGIN {
+                let (page, layer) = self.doc.add_page(
+                    Mm(PAGE_WIDTH),
+                    Mm(PAGE_HEIGHT),
+                    "Transcript",
+                );
+                self.layer = self.doc.get_page(page).get_layer(layer);
+                self.y = PAGE_HEIGHT - MARGIN;
+            }
+            let font = if bold { &self.bold } else { &self.regular };
+            for (x, text) in columns {
+                let (x, y) = (Mm(MARGIN + x), Mm(self.y));
+                self.layer.use_text(*text, 10.0, x, y, font);
+            }
+            self.y -= LINE_HEIGHT;
+        }
+
+        fn gap(&mut self) {
+            self.y -= LINE_HEIGHT;
+        }
+    }
+
+    fn transcript_pdf(
+        t: &Transcript,
+        verify_url: &str,
+    ) -> Result<Vec<u8>, printpdf::Error> {
+        let title = format!("Unofficial Transcript - {}", t.student.name);
+        let (doc, page, layer) = PdfDocument::new(
+            &title,
+            Mm(PAGE_WIDTH),
+            Mm(PAGE_HEIGHT),
+            "Transcript",
+        );
+        let mut w = Writer {
+            layer: doc.get_page(page).get_layer(layer),
+            regular: doc.add_builtin_font(BuiltinFont::Helvetica)?,
+            bold: doc.add_builtin_font(BuiltinFont::HelveticaBold)?,
+            doc: &doc,
+            y: PAGE_HEIGHT - MARGIN,
+        };
+
+        let s = &t.student;
+        w.line(
+            &[(0.0, "Alexandria University - Unofficial Transcript")],
+            true,
+        );
+        w.gap();
+        w.line(&[(0.0, "Name:"), (35.0, &s.name)], false);
+        w.line(&[(0.0, "Student No.:"), (35.0, &s.student_no)], false);
+        let program =
+            format!("{} ({}), bylaw {}", s.program, s.program_code, s.by_law);
+        w.line(&[(0.0, "Program:"), (35.0, &program)], false);
+        w.line(&[(0.0, "Issued at:"), (35.0, &t.issued_at)], false);
+
+        for term in &t.grades.terms {
+            w.gap();
+            let head = format!("Term {} - {}", term.term_no, term.term_abs);
+            w.line(&[(0.0, &head)], true);
+            for sub in &term.subjects {
+                let grade = match &sub.letter {
+                    Some(l) => format!("{l} ({:.2})", sub.gpa),
+                    None => format!("{:.2}", sub.gpa),
+                };
+                let credit = sub.credit.to_string();
+                let mut name = sub.name.clone();
+                if !sub.counted {
+                    name.push_str(" (superseded)");
+                }
+                let cols = [
+                    (0.0, &*sub.code),
+                    (25.0, &*name),
+                    (130.0, &*credit),
+                    (150.0, &*grade),
+                ];
+                w.line(&cols, false);
+            }
+            let summary = format!(
+                "Term GPA: {}   Cumulative GPA: {}   Credit hours: {}",
+                format_gpa(term.gpa),
+                format_gpa(term.cum_gpa),
+                term.cum_credits
+            );
+            w.line(&[(0.0, &summary)], false);
+        }
+
+        w.gap();
+        let total = format!(
+            "Cumulative GPA: {}   Earned credit hours: {}",
+            format_gpa(t.grades.gpa),
+            t.grades.earned_credits
+        );
+        w.line(&[(0.0, &total)], true);
+        w.gap();
+        w.line(&[(0.0, "Verify this transcript at:")], false);
+        w.line(&[(0.0, verify_url)], false);
+
+        doc.save_to_bytes()
+    }
+
+    async fn issue(
+        req: &HttpRequest,
+        pool: &sqlx::SqlitePool,
+    ) -> actix_web::Result<Result<Transcript, HttpResponse>> {
+        use actix_web::error::ErrorInternalServerError;
+
+        let Some(student_id) = crate::login::user_id_from_jwt(req) else {
+            return Ok(Err(HttpResponse::Unauthorized().finish()));
+        };
+        let transcript = issue_transcript(pool, student_id)
+            .await
+            .map_err(ErrorInternalServerError)?;
+        Ok(transcript.ok_or_else(|| HttpResponse::NotFound().finish()))
+    }
+
+    /// `GET /api/transcript.json`
+    pub async fn transcript_json_handler(
+        req: HttpRequest,
+        pool: web::Data<sqlx::SqlitePool>,
+    ) -> actix_web::Result<HttpResponse> {
+        let transcript = match issue(&req, &pool).await? {
+            Ok(transcript) => transcript,
+            Err(res) => return Ok(res),
+        };
+        Ok(HttpResponse::Ok()
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"transcript.json\"",
+            ))
+            .json(transcript))
+    }
+
+    /// `GET /api/transcript.pdf`
+    pub async fn transcript_pdf_handler(
+        req: HttpRequest,
+        pool: web::Data<sqlx::SqlitePool>,
+    ) -> actix_web::Result<HttpResponse> {
+        let transcript = match issue(&req, &pool).await? {
+            Ok(transcript) => transcript,
+            Err(res) => return Ok(res),
+        };
+
+        let info = req.connection_info();
+        let verify_url = format!(
+            "{}://{}/transcript/verify?code={}",
+            info.scheme(),
+            info.host(),
+            transcript.verification
+        );
+        let pdf = transcript_pdf(&transcript, &verify_url)
+            .map_err(actix_web::error::ErrorInternalServerError)?;
+
+        Ok(HttpResponse::Ok()
+            .content_type("application/pdf")
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"transcript.pdf\"",
+            ))
+            .body(pdf))
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub use export::{transcript_json_handler, transcript_pdf_handler};
+
+/// Public page for recipients to check a transcript against the server
+#[component]
+pub fn TranscriptVerifyPage() -> impl IntoView {
+    use crate::utils::create_query_signal;
+
+    let (code, set_code) = create_query_signal::<String>("code");
+    let transcript = create_resource(code, |code| async move {
+        match code {
+            Some(code) => verify_transcript(code).await.map(Some),
+            None => Ok(None),
+        }
+    });
+
+    view! {
+        <div class="py-5 px-7 mx-auto w-full max-w-[100rem]">
+            <h1 class="text-4xl mb-7">"Verify a Transcript"</h1>
+            <input
+                class="bg-secondary rounded px-1 w-full mb-4"
+                placeholder="Verification code"
+                prop:value=move || code().unwrap_or_default()
+                on:change=move |e| {
+                    let value = event_target_value(&e);
+                    set_code((!value.is_empty()).then_some(value))
+                }
+            />
+            <TransErr resource=transcript let:transcript>
+                {match (code().is_some(), transcript) {
+                    (_, Some(t)) => view! {
+                        <p class="text-green-600 pb-2">"This transcript was issued by the university"</p>
+                        <div class="flex flex-wrap gap-x-6 pb-4">
+                            <span>"Name: " <b>{&t.student.name}</b></span>
+                            <span>"Student No.: " <b>{&t.student.student_no}</b></span>
+                            <span>"Program: " <b>{&t.student.program}</b></span>
+                            <span>"Issued at: " <b>{&t.issued_at}</b></span>
+                            <span>"Cumulative GPA: " <b>{format_gpa(t.grades.gpa)}</b></span>
+                            <span>"Earned credit hours: " <b>{t.grades.earned_credits}</b></span>
+                        </div>
+                        <div class="flex flex-col gap-4">
+                            {t.grades.terms.iter().map(|term| view! { <TermCard term/> }).collect_view()}
+                        </div>
+                    }.into_view(),
+                    (true, None) => view! {
+                        <p class="text-red-500">"No transcript was issued with this code"</p>
+                    }.into_view(),
+                    (false, None) => ().into_view(),
+                }}
+            </TransErr>
+        </div>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod test {
+    use super::*;
+    use crate::grades::RepeatPolicy;
+
+    const KEY: &[u8] = b"transcript key";
+
+    fn transcript() -> Transcript {
+        let mut t = Transcript {
+            student: TranscriptStudent {
+                student_no: "1900123".to_owned(),
+                name: "Student".to_owned(),
+                program: "Computer Engineering".to_owned(),
+                program_code: "CE".to_owned(),
+                by_law: 2018,
+            },
+            issued_at: "2023-11-25T12:00:00+00:00".to_owned(),
+            grades: Grades {
+                policy: RepeatPolicy::Best,
+                terms: Vec::new(),
+                gpa: Some(3.2),
+                earned_credits: 36,
+            },
+            verification: String::new(),
+        };
+        t.sign(KEY).unwrap();
+        t
+    }
+
+    #[test]
+    fn round_trip() {
+        let t = transcript();
+        assert!(!t.verification.is_empty());
+        assert!(t.is_authentic(KEY));
+
+        let json = serde_json::to_string(&t).unwrap();
+        let parsed: Transcript = serde_json::from_str(&json).unwrap();
+        assert!(parsed.is_authentic(KEY));
+        assert!(!parsed.is_authentic(b"another key"));
+    }
+
+    #[test]
+    fn tampering() {
+        let mut t = transcript();
+        t.grades.gpa = Some(4.0);
+        assert!(!t.is_authentic(KEY));
+
+        let mut t = transcript();
+        t.student.name = "Someone Else".to_owned();
+        assert!(!t.is_authentic(KEY));
+
+        let mut t = transcript();
+        t.verification.pop();
+        assert!(!t.is_authentic(KEY));
+    }
+}
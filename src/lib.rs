@@ -3,13 +3,15 @@ mod components;
 mod theme;
 mod utils;
 
+mod audit;
+mod calendar;
 mod class;
 
-mod grades;
+pub mod grades;
 mod login;
 mod profile;
 pub mod registration;
-mod timetable;
+pub mod timetable;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
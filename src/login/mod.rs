@@ -99,9 +99,17 @@ async fn login(
 
 #[server]
 async fn logout() -> Result<(), ServerFnError> {
+    use crate::registration::rem_seats_ws::LogoutMsg;
+    use actix_broker::{Broker, SystemBroker};
     use actix_web::cookie::Cookie;
     use actix_web::http::header;
 
+    // ends the session's live connections in other tabs
+    let req = expect_context::<actix_web::HttpRequest>();
+    if let Some(session) = session_from_jwt(&req) {
+        Broker::<SystemBroker>::issue_async(LogoutMsg(session));
+    }
+
     let res = expect_context::<leptos_actix::ResponseOptions>();
     let cookie = Cookie::build("session", "")
         .path("/")
@@ -118,6 +126,30 @@ async fn logout() -> Result<(), ServerFnError> {
 
 #[cfg(feature = "ssr")]
 pub fn user_id_from_jwt(req: &actix_web::HttpRequest) -> Option<UserId> {
+    jwt_claims(req).map(|c| c.sub)
+}
+
+/// A login of a user, shared by the tabs of a browser. Timestamps are in
+/// unix seconds
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Session {
+    pub user_id: UserId,
+    pub issued_at: i64,
+    pub expires_at: i64,
+}
+
+#[cfg(feature = "ssr")]
+pub fn session_from_jwt(req: &actix_web::HttpRequest) -> Option<Session> {
+    jwt_claims(req).map(|c| Session {
+        user_id: c.sub,
+        issued_at: c.iat,
+        expires_at: c.exp,
+    })
+}
+
+#[cfg(feature = "ssr")]
+fn jwt_claims(req: &actix_web::HttpRequest) -> Option<JwtClaims> {
     use jsonwebtoken::{decode, DecodingKey, Validation};
 
     let sess = req.cookie("session")?;
@@ -130,7 +162,21 @@ pub fn user_id_from_jwt(req: &actix_web::HttpRequest) -> Option<UserId> {
     );
     let validation = Validation::default();
     let td = decode::<JwtClaims>(jwt, &key, &validation).ok()?;
-    Some(td.claims.sub)
+    Some(td.claims)
+}
+
+#[cfg(feature = "ssr")]
+pub async fn is_admin(
+    pool: &sqlx::SqlitePool,
+    user_id: UserId,
+) -> sqlx::Result<bool> {
+    let user_type = sqlx::query_scalar!(
+        "SELECT user_type FROM users WHERE id = ?",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(user_type.is_some_and(|t| t == "admin"))
 }
 
 #[server]
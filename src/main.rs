@@ -25,12 +25,36 @@ async fn main() -> std::io::Result<()> {
         .expect("Failed to run sqlx migrations");
 
     HttpServer::new(move || {
+        use uni_web::grades::transcript::{
+            transcript_json_handler, transcript_pdf_handler,
+        };
+        use uni_web::registration::registration_events_handler;
         use uni_web::registration::rem_seats_ws::rem_seats_ws;
+        use uni_web::timetable::ical::{
+            timetable_feed_handler, timetable_ics_handler,
+        };
+        use uni_web::timetable::print::timetable_pdf_handler;
 
         let leptos_options = &conf.leptos_options;
         let site_root = &leptos_options.site_root;
 
         App::new()
+            // must be registered before the server fns catch-all
+            .route("/api/timetable.ics", web::get().to(timetable_ics_handler))
+            .route(
+                "/api/timetable/{token}.ics",
+                web::get().to(timetable_feed_handler),
+            )
+            .route("/api/timetable.pdf", web::get().to(timetable_pdf_handler))
+            .route(
+                "/api/transcript.json",
+                web::get().to(transcript_json_handler),
+            )
+            .route("/api/transcript.pdf", web::get().to(transcript_pdf_handler))
+            .route(
+                "/api/registration/events",
+                web::get().to(registration_events_handler),
+            )
             .route("/api/{tail:.*}", leptos_actix::handle_server_fns())
             .route("/ws/rem_seats", web::get().to(rem_seats_ws))
             .service(Files::new("/pkg", format!("{site_root}/pkg")))
@@ -0,0 +1,648 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::validation::RegistrationResult;
+use super::SubjectId;
+
+#[cfg(feature = "ssr")]
+use {
+    super::{
+        credit_load::CreditLimits,
+        history::{record_events, EventSource},
+        prereq::Record,
+        validation::{RegistrationError, Violation},
+    },
+    crate::login::UserId,
+    std::collections::BTreeSet,
+};
+
+/// A single change to the student's registration
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Change {
+    Add(SubjectId),
+    Drop(SubjectId),
+    /// Moves the student to another choice of the same subject, the student
+    /// is registered in one of both at any time
+    Swap {
+        from: SubjectId,
+        to: SubjectId,
+    },
+}
+
+impl Change {
+    /// The choice the student is registered in by the change
+    pub fn added(&self) -> Option<SubjectId> {
+        match *self {
+            Self::Add(id) | Self::Swap { to: id, .. } => Some(id),
+            Self::Drop(_) => None,
+        }
+    }
+
+    /// The choice the student is no longer registered in after the change
+    pub fn removed(&self) -> Option<SubjectId> {
+        match *self {
+            Self::Drop(id) | Self::Swap { from: id, .. } => Some(id),
+            Self::Add(_) => None,
+        }
+    }
+}
+
+/// Applies `changes` to the student's `term_subscribers` in order, if the
+/// resulting registration passes validation within the student's credit
+/// `limits` and against their academic `record`. Either all or none apply.
+/// Rows left untouched keep their `registered_at`, and every change is
+/// logged to `registration_events` along with its `source`.
+/// Returns the choices that were added or dropped.
+#[cfg(feature = "ssr")]
+pub(crate) async fn changes_tx(
+    pool: &sqlx::SqlitePool,
+    student_id: UserId,
+    changes: &[Change],
+    limits: &CreditLimits,
+    record: &Record,
+    source: &EventSource,
+) -> sqlx::Result<Result<Vec<SubjectId>, RegistrationError>> {
+    use super::server_fns::check_registration;
+
+    if changes.is_empty() {
+        return Ok(Ok(Vec::new()));
+    }
+
+    // Starting with a write locks the db for the rest of the transaction,
+    // so the checks below can't race other registrations.
+    // The db rejects any write that would exceed `max_seats`
+    let mut tx = pool.begin().await?;
+    for &change in changes {
+        if let Err(e) = apply(&mut *tx, student_id, change).await? {
+            tx.rollback().await?;
+            return Ok(Err(e));
+        }
+    }
+
+    let new: BTreeSet<SubjectId> = sqlx::query_scalar!(
+        r#"
+            SELECT term_subject_id AS "id: SubjectId"
+            FROM term_subscribers
+            WHERE student_id = ?
+        "#,
+        student_id
+    )
+    .fetch_all(&mut *tx)
+    .await?
+    .into_iter()
+    .collect();
+    let added: BTreeSet<_> = changes.iter().filter_map(Change::added).collect();
+    let prev: BTreeSet<_> = new
+        .difference(&added)
+        .copied()
+        .chain(changes.iter().filter_map(Change::removed))
+        .collect();
+
+    if !new.is_empty() {
+        let mut violations =
+            check_registration(&mut *tx, &new, &prev, limits, record).await?;
+        // seats were already taken above, the db guarantees they were free
+        violations.retain(|v| !matches!(v, Violation::NoSeats(_)));
+        if !violations.is_empty() {
+            tx.rollback().await?;
+            return Ok(Err(RegistrationError::Rejected(violations)));
+        }
+    }
+
+    record_events(&mut *tx, student_id, changes, source).await?;
+
+    // waiting for a choice the student is now registered in is pointless
+    for id in &added {
+        sqlx::query!(
+            "DELETE FROM waitlist WHERE student_id = ? AND term_subject_id = ?",
+            student_id,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(Ok(new.symmetric_difference(&prev).copied().collect()))
+}
+
+/// Applies a single change, mapping the constraints the db enforces
+/// to registration errors
+#[cfg(feature = "ssr")]
+async fn apply(
+    conn: &mut sqlx::SqliteConnection,
+    student_id: UserId,
+    change: Change,
+) -> sqlx::Result<Result<(), RegistrationError>> {
+    let result = match change {
+        Change::Add(id) => {
+            sqlx::query!(
+                r#"
+                    INSERT INTO term_subscribers (student_id, term_subject_id)
+                    VALUES (?, ?)
+                "#,
+                student_id,
+                id
+            )
+            .execute(&mut *conn)
+            .await
+        }
+        Change::Drop(id) => {
+            sqlx::query!(
+                r#"
+                    DELETE FROM term_subscribers
+                    WHERE student_id = ? AND term_subject_id = ?
+                "#,
+                student_id,
+                id
+            )
+            .execute(&mut *conn)
+            .await
+        }
+        // a single statement, the `move_seat` trigger moves the seat along
+        Change::Swap { from, to } => {
+            sqlx::query!(
+                r#"
+                    UPDATE term_subscribers
+                    SET term_subject_id = ?3
+                    WHERE student_id = ?1 AND term_subject_id = ?2
+                      AND (SELECT subject_id FROM term_subjects WHERE id = ?2)
+                        = (SELECT subject_id FROM term_subjects WHERE id = ?3)
+                "#,
+                student_id,
+                from,
+                to
+            )
+            .execute(&mut *conn)
+            .await
+        }
+    };
+
+    let rejected = |v| Ok(Err(RegistrationError::Rejected(vec![v])));
+    match (result, change) {
+        (Ok(r), Change::Drop(id)) if r.rows_affected() == 0 => {
+            Ok(Err(RegistrationError::NotRegistered(id)))
+        }
+        (Ok(r), Change::Swap { from, to }) if r.rows_affected() == 0 => {
+            let registered = sqlx::query_scalar!(
+                r#"
+                    SELECT COUNT(*) > 0 AS "registered!: bool"
+                    FROM term_subscribers
+                    WHERE student_id = ? AND term_subject_id = ?
+                "#,
+                student_id,
+                from
+            )
+            .fetch_one(&mut *conn)
+            .await?;
+            Ok(Err(match registered {
+                true => RegistrationError::InvalidSwap { from, to },
+                false => RegistrationError::NotRegistered(from),
+            }))
+        }
+        (Ok(_), _) => Ok(Ok(())),
+        (Err(sqlx::Error::Database(e)), change) => {
+            // only adding a choice can break a constraint
+            let Some(id) = change.added() else {
+                return Err(sqlx::Error::Database(e));
+            };
+            if e.is_check_violation() {
+                rejected(Violation::NoSeats(id))
+            } else if e.is_unique_violation() {
+                Ok(Err(RegistrationError::AlreadyRegistered(id)))
+            } else if e.is_foreign_key_violation() {
+                rejected(Violation::UnknownSubject(id))
+            } else {
+                Err(sqlx::Error::Database(e))
+            }
+        }
+        (Err(e), _) => Err(e),
+    }
+}
+
+/// Applies `changes` to the registration of the logged in student,
+/// then registers waitlisted students in the freed seats.
+/// The credit minimum is only enforced when saving the whole registration
+#[cfg(feature = "ssr")]
+async fn change_registration(
+    changes: Vec<Change>,
+    save: bool,
+) -> Result<RegistrationResult, ServerFnError> {
+    use super::credit_load::credit_limits;
+    use super::prereq::student_record;
+    use super::rem_seats_ws::WaitlistMsg;
+    use super::server_fns::get_rem_seats;
+    use super::waitlist::promote_waitlisted;
+    use super::window::window_state;
+    use actix_broker::{Broker, SystemBroker};
+
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Ok(Err(RegistrationError::Unauthorized));
+    };
+
+    if !window_state(&pool, Some(student_id))
+        .await?
+        .accepts_changes()
+    {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::FORBIDDEN);
+        return Ok(Err(RegistrationError::Closed));
+    }
+
+    let limits = credit_limits(&pool, student_id).await?;
+    let limits = match save {
+        true => limits,
+        false => limits.without_minimum(),
+    };
+    let record = student_record(&pool, student_id).await?;
+    let source = EventSource::from_request(&req, student_id);
+    let diff = match changes_tx(
+        &pool, student_id, &changes, &limits, &record, &source,
+    )
+    .await?
+    {
+        Ok(diff) => diff,
+        Err(e) => {
+            expect_context::<leptos_actix::ResponseOptions>()
+                .set_status(actix_web::http::StatusCode::UNPROCESSABLE_ENTITY);
+            return Ok(Err(e));
+        }
+    };
+
+    if diff.is_empty() {
+        return Ok(Ok(()));
+    }
+
+    let freed = changes.iter().filter_map(Change::removed).collect();
+    let promoted = promote_waitlisted(&pool, freed).await?;
+    let changed: Vec<_> = BTreeSet::from_iter(diff.into_iter().chain(promoted))
+        .into_iter()
+        .collect();
+
+    // Broadcast `rem_seats` and waitlist changes to ws actors
+    Broker::<SystemBroker>::issue_async(
+        get_rem_seats(&changed, pool.clone()).await?,
+    );
+    Broker::<SystemBroker>::issue_async(WaitlistMsg {
+        students: vec![student_id],
+        choices: changed,
+    });
+    Ok(Ok(()))
+}
+
+/// Applies every change, or none if any fails.
+/// Used to save the registration page's changes at once
+#[server(encoding = "Cbor")]
+pub async fn apply_changes(
+    changes: Vec<Change>,
+) -> Result<RegistrationResult, ServerFnError> {
+    change_registration(changes, true).await
+}
+
+#[server]
+pub async fn add_subject(
+    id: SubjectId,
+) -> Result<RegistrationResult, ServerFnError> {
+    change_registration(vec![Change::Add(id)], false).await
+}
+
+#[server]
+pub async fn drop_subject(
+    id: SubjectId,
+) -> Result<RegistrationResult, ServerFnError> {
+    change_registration(vec![Change::Drop(id)], false).await
+}
+
+/// Moves the student from `from` to `to`, another choice of the same subject
+#[server]
+pub async fn swap_section(
+    from: SubjectId,
+    to: SubjectId,
+) -> Result<RegistrationResult, ServerFnError> {
+    change_registration(vec![Change::Swap { from, to }], false).await
+}
+
+#[cfg(all(test, feature = "ssr"))]
+pub(super) mod test {
+    use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+    use sqlx::SqlitePool;
+
+    use super::SubjectId;
+    use super::{changes_tx, Change, CreditLimits, EventSource, Record};
+    use crate::login::UserId;
+    use crate::registration::credit_load::{credit_limits, CreditLoadPolicy};
+    use crate::registration::validation::{RegistrationError, Violation};
+
+    const STUDENTS: i64 = 50;
+    const MAX_SEATS: i64 = 5;
+
+    /// Deletes the SQLite file of `seeded_pool`, and its WAL files, on drop
+    pub(crate) struct TempDb(std::path::PathBuf);
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = self.0.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    /// Creates a migrated SQLite file with `STUDENTS` users, a term subject
+    /// of `MAX_SEATS` seats, and another section of it with a single seat.
+    /// The file lives as long as the returned `TempDb`
+    pub(crate) async fn seeded_pool() -> (SqlitePool, TempDb) {
+        let path = std::env::temp_dir()
+            .join(format!("uni_web_test_{}.db", uuid::Uuid::new_v4()));
+        let options = SqliteConnectOptions::new()
+            .filename(&path)
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(16)
+            .connect_with(options)
+            .await
+            .unwrap();
+        sqlx::migrate!().run(&pool).await.unwrap();
+
+        sqlx::query(&format!(
+            r#"
+                WITH RECURSIVE n(i) AS (
+                    SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < {STUDENTS}
+                )
+                INSERT INTO users (id, username, password, email, name)
+                SELECT i, 'student' || i, '', '', '' FROM n;
+
+                INSERT INTO subjects (id, name, code, level, credit)
+                VALUES (1, 'Subject', 'SUB101', 1, 3);
+                INSERT INTO professors (id, name) VALUES (1, 'Prof');
+                INSERT INTO locations (id, building, floor, room)
+                VALUES (1, 'ssp', 0, 'A1');
+                INSERT INTO classes (
+                    id, type, day_of_week, period_start, period_end,
+                    subject_id, location_id
+                )
+                VALUES
+                    (1, 'lec', 'sunday', 0, 1, 1, 1),
+                    (2, 'lec', 'monday', 0, 1, 1, 1);
+                INSERT INTO term_subjects (
+                    id, max_seats, group_no, sec_no, subject_id, prof_id, lec_id
+                )
+                VALUES
+                    (1, {MAX_SEATS}, 1, 1, 1, 1, 1),
+                    (2, 1, 2, 1, 1, 1, 2);
+            "#
+        ))
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        (pool, TempDb(path))
+    }
+
+    async fn taken_seats(pool: &SqlitePool) -> (i64, i64) {
+        sqlx::query_as(
+            r#"
+                SELECT
+                    (SELECT COUNT(*) FROM term_subscribers),
+                    (SELECT taken_seats FROM term_subjects WHERE id = 1)
+            "#,
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn change(
+        pool: &SqlitePool,
+        student: i64,
+        change: Change,
+    ) -> Result<Vec<SubjectId>, RegistrationError> {
+        let limits = CreditLimits::default();
+        let record = Record::default();
+        let source = EventSource::default();
+        changes_tx(
+            pool,
+            UserId::from(student),
+            &[change],
+            &limits,
+            &record,
+            &source,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn concurrent_registrations_never_exceed_max_seats() {
+        let (pool, _db) = seeded_pool().await;
+        let add = Change::Add(SubjectId(1));
+
+        let results = futures::future::join_all(
+            (1..=STUDENTS).map(|student| change(&pool, student, add)),
+        )
+        .await;
+
+        let (registered, rejected): (Vec<_>, Vec<_>) =
+            results.into_iter().partition(|r| r.is_ok());
+        assert_eq!(registered.len() as i64, MAX_SEATS);
+        for r in rejected {
+            assert_eq!(
+                r,
+                Err(RegistrationError::Rejected(vec![Violation::NoSeats(
+                    SubjectId(1)
+                )]))
+            );
+        }
+        assert_eq!(taken_seats(&pool).await, (MAX_SEATS, MAX_SEATS));
+    }
+
+    #[actix_web::test]
+    async fn db_rejects_oversubscription() {
+        let (pool, _db) = seeded_pool().await;
+        let insert = |student: i64| {
+            sqlx::query(
+                r#"
+                    INSERT INTO term_subscribers (student_id, term_subject_id)
+                    VALUES (?, 1)
+                "#,
+            )
+            .bind(student)
+            .execute(&pool)
+        };
+
+        for student in 1..=MAX_SEATS {
+            insert(student).await.unwrap();
+        }
+        assert!(insert(MAX_SEATS + 1).await.is_err());
+        assert_eq!(taken_seats(&pool).await, (MAX_SEATS, MAX_SEATS));
+
+        sqlx::query("DELETE FROM term_subscribers WHERE student_id = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+        insert(MAX_SEATS + 1).await.unwrap();
+        assert_eq!(taken_seats(&pool).await, (MAX_SEATS, MAX_SEATS));
+    }
+
+    #[actix_web::test]
+    async fn swaps_keep_a_seat() {
+        let (pool, _db) = seeded_pool().await;
+        let (a, b) = (SubjectId(1), SubjectId(2));
+        let swap = Change::Swap { from: a, to: b };
+
+        assert_eq!(change(&pool, 1, Change::Add(a)).await, Ok(vec![a]));
+        assert_eq!(change(&pool, 2, Change::Add(b)).await, Ok(vec![b]));
+        // the only seat of `b` is taken, the student keeps `a`
+        assert_eq!(
+            change(&pool, 1, swap).await,
+            Err(RegistrationError::Rejected(vec![Violation::NoSeats(b)]))
+        );
+        assert_eq!(taken_seats(&pool).await, (2, 1));
+
+        assert_eq!(change(&pool, 2, Change::Drop(b)).await, Ok(vec![b]));
+        assert_eq!(change(&pool, 1, swap).await, Ok(vec![a, b]));
+        assert_eq!(taken_seats(&pool).await, (1, 0));
+
+        assert_eq!(
+            change(&pool, 1, Change::Drop(a)).await,
+            Err(RegistrationError::NotRegistered(a))
+        );
+        assert_eq!(
+            change(&pool, 1, Change::Add(a)).await,
+            Err(RegistrationError::Rejected(vec![
+                Violation::DuplicateSubject(a, b)
+            ]))
+        );
+    }
+
+    #[actix_web::test]
+    async fn changes_are_logged() {
+        let (pool, _db) = seeded_pool().await;
+        let (a, b) = (SubjectId(1), SubjectId(2));
+
+        change(&pool, 1, Change::Add(a)).await.unwrap();
+        change(&pool, 1, Change::Swap { from: a, to: b })
+            .await
+            .unwrap();
+        // rejected changes leave no trace
+        change(&pool, 1, Change::Drop(a)).await.unwrap_err();
+        change(&pool, 1, Change::Drop(b)).await.unwrap();
+
+        let events: Vec<(String, i64, Option<i64>)> = sqlx::query_as(
+            r#"
+                SELECT action, term_subject_id, swapped_from
+                FROM registration_events
+                WHERE student_id = 1
+                ORDER BY id
+            "#,
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                ("add".into(), 1, None),
+                ("swap".into(), 2, Some(1)),
+                ("drop".into(), 2, None),
+            ]
+        );
+
+        let delete = sqlx::query("DELETE FROM registration_events")
+            .execute(&pool)
+            .await;
+        assert!(delete.is_err());
+    }
+
+    #[actix_web::test]
+    async fn registrations_outlive_eligibility() {
+        let (pool, _db) = seeded_pool().await;
+        let (a, b) = (SubjectId(1), SubjectId(2));
+
+        change(&pool, 1, Change::Add(a)).await.unwrap();
+        // e.g. the subject was moved above the student's level
+        sqlx::query("UPDATE subjects SET level = 2 WHERE id = 1")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let too_high = vec![Violation::LevelTooHigh(a)];
+        assert_eq!(
+            change(&pool, 2, Change::Add(a)).await,
+            Err(RegistrationError::Rejected(too_high))
+        );
+        let swap = Change::Swap { from: a, to: b };
+        assert_eq!(change(&pool, 1, swap).await, Ok(vec![a, b]));
+        assert_eq!(change(&pool, 1, Change::Drop(b)).await, Ok(vec![b]));
+    }
+
+    #[actix_web::test]
+    async fn credit_minimum() {
+        let (pool, _db) = seeded_pool().await;
+        sqlx::query(
+            r#"
+                INSERT INTO programs (id, name, code, by_law)
+                VALUES (1, 'Program', 'PRG', 2018);
+                INSERT INTO credit_load_policies (
+                    by_law, min_credits, max_credits,
+                    overload_gpa, overload_credits,
+                    probation_gpa, probation_credits
+                )
+                VALUES (2018, 12, 18, 3.0, 21, 2.0, 12);
+                INSERT INTO student_profile (
+                    id, name_en, name_ar, program_id, nationality
+                )
+                VALUES (1, '', '', 1, '');
+                UPDATE users SET profile_id = 1 WHERE id = 1;
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let student = UserId::from(1);
+        let limits = credit_limits(&pool, student).await.unwrap();
+        assert_eq!((limits.min, limits.max), (12, 18));
+        let graduating = CreditLoadPolicy {
+            min_credits: 12,
+            max_credits: 18,
+            overload_gpa: 3.0,
+            overload_credits: 21,
+            probation_gpa: 2.0,
+            probation_credits: 12,
+            graduating_extra: 3,
+        }
+        .limits(None, Some(3));
+
+        let record = Record::default();
+        let source = EventSource::default();
+        let change = |change, limits| {
+            let (pool, record, source) = (&pool, &record, &source);
+            async move {
+                changes_tx(pool, student, &[change], &limits, record, source)
+                    .await
+                    .unwrap()
+            }
+        };
+        // 3 credit hours, below the minimum
+        let a = SubjectId(1);
+        let single = limits.without_minimum();
+        assert_eq!(change(Change::Add(a), single).await, Ok(vec![a]));
+        assert_eq!(change(Change::Drop(a), single).await, Ok(vec![a]));
+        assert_eq!(
+            change(Change::Add(a), limits).await,
+            Err(RegistrationError::Rejected(vec![
+                Violation::CreditMinimum { selected: 3, min: 12 }
+            ]))
+        );
+        assert_eq!(change(Change::Add(a), graduating).await, Ok(vec![a]));
+        // an empty registration has no minimum
+        assert_eq!(change(Change::Drop(a), limits).await, Ok(vec![a]));
+    }
+}
@@ -1,23 +1,33 @@
 use leptos::*;
 
 use super::subjects_signal::SubjectsSignal;
-use super::{Seats, Subject};
+use super::waitlist::{JoinWaitlist, LeaveWaitlist};
+use super::window::WindowState;
+use super::{Seats, Subject, SubjectId, Waitlist, WindowStateMemo};
 use crate::class::{Class, Type as ClassType};
 use crate::icon;
 
 #[component]
 pub fn ClassCard(subject: Subject) -> impl IntoView {
-    let Subject { id, max_seats, group, lec, tut, lab } = subject;
+    let Subject { id, max_seats, group, lec, tut, lab, .. } = subject;
     let subjects_ctx = expect_context::<SubjectsSignal>();
 
     let on_click = move |_| subjects_ctx.toggle(id);
     let is_selected = subjects_ctx.is_selected(id);
     let has_changed = subjects_ctx.has_changed(id);
     let has_collisions = subjects_ctx.has_collisions(id);
+    let violations = subjects_ctx.violations(id);
 
     let prof = extract_prof_name(&lec);
     let sec_no = sec_no(&tut, &lab);
     let rem_seats = Memo::new(rem_seats(id, is_selected, has_changed));
+    let WindowStateMemo(window_state) = expect_context::<WindowStateMemo>();
+    // waitlists only accept students while registration is open
+    let joinable = Signal::derive(move || {
+        rem_seats() == Some(0)
+            && !is_selected()
+            && window_state() == WindowState::Open
+    });
 
     let format_class_time = |c: Class| {
         view! {
@@ -51,8 +61,9 @@ pub fn ClassCard(subject: Subject) -> impl IntoView {
                     flex flex-col gap-1 \
                     data-[selected]:border-indigo-300 data-[invalid]:!border-red-300 \
                     "
+            id=card_id(id)
             data-selected=is_selected
-            data-invalid=has_collisions
+            data-invalid=move || has_collisions() || !violations().is_empty()
         >
             <p class="uppercase text-indigo-500 dark:text-indigo-300">
                 {"Group "} {group}
@@ -76,13 +87,101 @@ pub fn ClassCard(subject: Subject) -> impl IntoView {
                 }
                 {move || if is_selected() { "Added" } else { "Add" }}
                 <span class="text-xs font-thin">
-                    {move || format!(" ({} / {})", rem_seats(), max_seats)}
+                    {move || match rem_seats() {
+                        Some(rem) => format!(" ({rem} / {max_seats})"),
+                        None => format!(" (? / {max_seats})"),
+                    }}
                 </span>
             </button>
+            <WaitlistButton id joinable/>
+            <ul class="text-xs text-red-500">
+                {move || {
+                    violations()
+                        .into_iter()
+                        .map(|v| view! { <li>{v.to_string()}</li> })
+                        .collect_view()
+                }}
+            </ul>
         </div>
     }
 }
 
+fn card_id(id: SubjectId) -> String {
+    format!("choice-{id}")
+}
+
+/// Scrolls to the card of the choice, expanding its accordion item.
+/// Does nothing if the card isn't rendered, e.g. when it's filtered out
+pub fn reveal(id: SubjectId) {
+    use wasm_bindgen::JsCast;
+
+    let Some(card) = document().get_element_by_id(&card_id(id)) else {
+        return;
+    };
+    let collapsed = card
+        .closest("li")
+        .ok()
+        .flatten()
+        .and_then(|li| {
+            li.query_selector(":scope > button[aria-expanded=false]")
+                .ok()
+        })
+        .flatten();
+    if let Some(button) = collapsed {
+        button.unchecked_into::<web_sys::HtmlElement>().click();
+    }
+    card.scroll_into_view();
+}
+
+/// Lets the student queue for a seat in a full choice, or leave the queue
+/// they're in even if it's no longer `joinable`
+#[component]
+fn WaitlistButton(id: SubjectId, joinable: Signal<bool>) -> impl IntoView {
+    let Waitlist(waitlist) = expect_context::<Waitlist>();
+    let position = Memo::new(move |_| {
+        waitlist.with(|w| {
+            w.iter().find_map(|&(sid, pos)| (sid == id).then_some(pos))
+        })
+    });
+
+    let join = create_server_action::<JoinWaitlist>();
+    let leave = create_server_action::<LeaveWaitlist>();
+    let pending = Signal::derive(move || join.pending()() || leave.pending()());
+    // only one of the actions has a value, the other is cleared on dispatch
+    let error = move || match (join.value()(), leave.value()()) {
+        (Some(Ok(Err(e))), _) => Some(e.to_string()),
+        (Some(Err(e)), _) | (_, Some(Err(e))) => Some(e.to_string()),
+        _ => None,
+    };
+
+    let on_click = move |_| {
+        if position().is_some() {
+            join.value().set(None);
+            leave.dispatch(LeaveWaitlist { id });
+        } else {
+            leave.value().set(None);
+            join.dispatch(JoinWaitlist { id });
+        }
+    };
+
+    view! {
+        <Show when=move || joinable() || position().is_some()>
+            <button
+                type="button"
+                class="btn-primary-outline"
+                disabled=pending
+                on:click=on_click
+            >
+                {move || match position() {
+                    Some(pos) => format!("Waitlisted #{pos} - Leave"),
+                    None => "Join Waitlist".to_owned(),
+                }}
+            </button>
+            <p class="text-xs text-red-500">{error}</p>
+        </Show>
+    }
+}
+
 fn format_period((mut st, end): (usize, usize)) -> String {
     st += 1;
     if st == end {
@@ -112,21 +211,21 @@ fn sec_no<'a>(
     )
 }
 
+/// The remaining seats of the choice, `None` until the server sends them
 fn rem_seats(
-    id: super::SubjectId,
+    id: SubjectId,
     is_selected: Signal<bool>,
     has_changed: Signal<bool>,
-) -> impl Fn(Option<&u32>) -> u32 {
+) -> impl Fn(Option<&Option<u32>>) -> Option<u32> {
     let seats_ctx = expect_context::<Seats>();
     move |prev| {
         let ws_count = seats_ctx.with(|s| {
             s.iter()
                 .find_map(|&(sid, seats)| (sid == id).then_some(seats))
-                .or(prev.cloned())
-                .unwrap_or_default()
-        });
+                .or(prev.copied().flatten())
+        })?;
 
-        if has_changed() {
+        Some(if has_changed() {
             if is_selected() {
                 ws_count.checked_sub(1).unwrap_or_default()
             } else {
@@ -134,6 +233,6 @@ fn rem_seats(
             }
         } else {
             ws_count
-        }
+        })
     }
 }
@@ -0,0 +1,222 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+use crate::login::UserId;
+
+/// The academic standing that decided the student's credit limits
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Standing {
+    Regular,
+    /// The student's GPA allows registering more than the regular maximum
+    Overload,
+    /// The student's GPA caps their registration below the regular maximum
+    Probation,
+    /// The student can graduate this term, and may register everything left
+    Graduating,
+}
+
+impl std::fmt::Display for Standing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Regular => write!(f, "Regular"),
+            Self::Overload => write!(f, "Overload"),
+            Self::Probation => write!(f, "Probation"),
+            Self::Graduating => write!(f, "Graduating"),
+        }
+    }
+}
+
+/// The credit hours a student may register in a term, inclusive
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CreditLimits {
+    pub min: u32,
+    pub max: u32,
+    pub standing: Standing,
+}
+
+impl CreditLimits {
+    pub fn allows(&self, credits: u32) -> bool {
+        (self.min..=self.max).contains(&credits)
+    }
+
+    /// The limits of a single change, the minimum only applies to a saved
+    /// registration as a whole, which is built up one change at a time
+    pub fn without_minimum(self) -> Self {
+        Self { min: 0, ..self }
+    }
+}
+
+/// The limits of students whose bylaw has no credit-load policy
+#[cfg(feature = "ssr")]
+impl Default for CreditLimits {
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: super::validation::MAX_CREDIT_HOURS,
+            standing: Standing::Regular,
+        }
+    }
+}
+
+/// A row of `credit_load_policies`
+#[cfg(feature = "ssr")]
+#[derive(Clone, Debug)]
+pub(crate) struct CreditLoadPolicy {
+    pub min_credits: u32,
+    pub max_credits: u32,
+    pub overload_gpa: f64,
+    pub overload_credits: u32,
+    pub probation_gpa: f64,
+    pub probation_credits: u32,
+    pub graduating_extra: u32,
+}
+
+#[cfg(feature = "ssr")]
+impl CreditLoadPolicy {
+    /// `gpa` is the cumulative GPA, `None` before the first graded term.
+    /// `remaining` are the credits left to graduate, `None` if the student's
+    /// program has no curriculum
+    pub fn limits(
+        &self,
+        gpa: Option<f64>,
+        remaining: Option<u32>,
+    ) -> CreditLimits {
+        let limits = match gpa {
+            Some(gpa) if gpa < self.probation_gpa => CreditLimits {
+                min: self.min_credits.min(self.probation_credits),
+                max: self.probation_credits,
+                standing: Standing::Probation,
+            },
+            Some(gpa) if gpa >= self.overload_gpa => CreditLimits {
+                min: self.min_credits,
+                max: self.overload_credits,
+                standing: Standing::Overload,
+            },
+            _ => CreditLimits {
+                min: self.min_credits,
+                max: self.max_credits,
+                standing: Standing::Regular,
+            },
+        };
+
+        match remaining {
+            Some(rem) if rem <= limits.max + self.graduating_extra => {
+                CreditLimits {
+                    min: 0,
+                    max: limits.max.max(rem),
+                    standing: Standing::Graduating,
+                }
+            }
+            _ => limits,
+        }
+    }
+}
+
+/// Returns the credit limits of the student for the current term
+#[cfg(feature = "ssr")]
+pub(crate) async fn credit_limits(
+    pool: &sqlx::SqlitePool,
+    student_id: UserId,
+) -> sqlx::Result<CreditLimits> {
+    let policy = sqlx::query_as!(
+        CreditLoadPolicy,
+        r#"
+            SELECT clp.min_credits AS "min_credits: u32",
+                   clp.max_credits AS "max_credits: u32",
+                   clp.overload_gpa,
+                   clp.overload_credits AS "overload_credits: u32",
+                   clp.probation_gpa,
+                   clp.probation_credits AS "probation_credits: u32",
+                   clp.graduating_extra AS "graduating_extra: u32"
+            FROM users AS u
+            INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+            INNER JOIN programs AS p ON p.id = sp.program_id
+            INNER JOIN credit_load_policies AS clp ON clp.by_law = p.by_law
+            WHERE u.id = ?
+        "#,
+        student_id
+    )
+    .fetch_optional(pool)
+    .await?;
+    let Some(policy) = policy else {
+        return Ok(CreditLimits::default());
+    };
+
+    let gpa = crate::grades::student_grades(pool, student_id).await?.gpa;
+    let remaining = crate::audit::degree_audit(pool, student_id)
+        .await?
+        .map(|a| a.remaining_credits);
+    Ok(policy.limits(gpa, remaining))
+}
+
+#[server(encoding = "GetJson")]
+pub async fn get_credit_limits() -> Result<CreditLimits, ServerFnError> {
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Err(ServerFnError::ServerError("Auth Error".into()));
+    };
+
+    Ok(credit_limits(&pool, student_id).await?)
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod test {
+    use super::*;
+
+    const POLICY: CreditLoadPolicy = CreditLoadPolicy {
+        min_credits: 12,
+        max_credits: 18,
+        overload_gpa: 3.0,
+        overload_credits: 21,
+        probation_gpa: 2.0,
+        probation_credits: 12,
+        graduating_extra: 3,
+    };
+
+    fn limits(min: u32, max: u32, standing: Standing) -> CreditLimits {
+        CreditLimits { min, max, standing }
+    }
+
+    #[test]
+    fn standing() {
+        use Standing::*;
+        assert_eq!(POLICY.limits(None, None), limits(12, 18, Regular));
+        assert_eq!(
+            POLICY.limits(Some(2.0), Some(100)),
+            limits(12, 18, Regular)
+        );
+        assert_eq!(
+            POLICY.limits(Some(3.0), Some(100)),
+            limits(12, 21, Overload)
+        );
+        assert_eq!(POLICY.limits(Some(1.9), None), limits(12, 12, Probation));
+    }
+
+    #[test]
+    fn graduating() {
+        use Standing::*;
+        assert_eq!(
+            POLICY.limits(Some(2.5), Some(21)),
+            limits(0, 21, Graduating)
+        );
+        assert_eq!(
+            POLICY.limits(Some(2.5), Some(6)),
+            limits(0, 18, Graduating)
+        );
+        assert_eq!(POLICY.limits(Some(2.5), Some(22)), limits(12, 18, Regular));
+        // probation shrinks the window in which the exception applies
+        assert_eq!(
+            POLICY.limits(Some(1.5), Some(15)),
+            limits(0, 15, Graduating)
+        );
+        assert_eq!(
+            POLICY.limits(Some(1.5), Some(16)),
+            limits(12, 12, Probation)
+        );
+    }
+}
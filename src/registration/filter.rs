@@ -0,0 +1,454 @@
+use leptos::*;
+use leptos_router::*;
+use std::collections::BTreeSet;
+
+use super::subjects_signal::SubjectsSignal;
+use super::{Seats, Subject, SubjectChoices, SubjectId};
+use crate::class::{Building, Class, DayOfWeek, Type as ClassType};
+use crate::icon;
+
+/// The query keys of the filter
+const KEYS: [&str; 9] = [
+    "q",
+    "group",
+    "section",
+    "day",
+    "from",
+    "to",
+    "building",
+    "seats",
+    "no_collide",
+];
+
+/// Filters of the registration accordion, read from the url query so
+/// filtered views can be shared
+#[derive(Clone, PartialEq, Default)]
+pub struct SubjectFilter {
+    /// Matched against the code, name and professors of a subject
+    pub text: String,
+    pub group: Option<u8>,
+    pub section: Option<u8>,
+    /// Keeps choices with a class on this day
+    pub day: Option<DayOfWeek>,
+    /// Keeps choices without classes before this period, 0-indexed
+    pub from: Option<usize>,
+    /// Keeps choices without classes after this period, 0-indexed
+    pub to: Option<usize>,
+    /// Keeps choices with a class in this building
+    pub building: Option<Building>,
+    /// Hides full choices
+    pub has_seats: bool,
+    /// Hides choices colliding with the selected subjects
+    pub no_collisions: bool,
+}
+
+impl SubjectFilter {
+    /// Reads the filter from the url query, missing filters are unset
+    pub fn from_query(query: &ParamsMap) -> Self {
+        fn get<T: std::str::FromStr>(
+            query: &ParamsMap,
+            key: &str,
+        ) -> Option<T> {
+            query.get(key).and_then(|v| v.parse().ok())
+        }
+        // periods are 1-indexed in the url
+        let period =
+            |key| get::<usize>(query, key).and_then(|p| p.checked_sub(1));
+
+        Self {
+            text: query.get("q").cloned().unwrap_or_default(),
+            group: get(query, "group"),
+            section: get(query, "section"),
+            day: get(query, "day"),
+            from: period("from"),
+            to: period("to"),
+            building: get(query, "building"),
+            has_seats: get(query, "seats").unwrap_or_default(),
+            no_collisions: get(query, "no_collide").unwrap_or_default(),
+        }
+    }
+
+    /// Returns true if any filter other than the text is set
+    fn filters_choices(&self) -> bool {
+        let text = self.text.clone();
+        *self != Self { text, ..Default::default() }
+    }
+
+    /// Returns the subject with only the choices passing the filter,
+    /// `None` if the subject is hidden altogether.
+    /// Locked subjects have no choices, so only the text applies to them
+    pub fn apply(
+        &self,
+        sc: &SubjectChoices,
+        rem_seats: impl Fn(SubjectId) -> Option<u32>,
+        collides: impl Fn(SubjectId) -> bool,
+    ) -> Option<SubjectChoices> {
+        let text = self.text.trim().to_lowercase();
+        let subject_matches = text.is_empty()
+            || sc.code.to_lowercase().contains(&text)
+            || sc.name.to_lowercase().contains(&text);
+
+        if sc.is_locked() {
+            return (subject_matches && !self.filters_choices())
+                .then(|| sc.clone());
+        }
+
+        let choices: Vec<_> = sc
+            .choices
+            .iter()
+            .filter(|s| {
+                (subject_matches || prof_matches(s, &text))
+                    && self.choice_matches(s, &rem_seats, &collides)
+            })
+            .cloned()
+            .collect();
+        (!choices.is_empty()).then(|| SubjectChoices { choices, ..sc.clone() })
+    }
+
+    fn choice_matches(
+        &self,
+        s: &Subject,
+        rem_seats: impl Fn(SubjectId) -> Option<u32>,
+        collides: impl Fn(SubjectId) -> bool,
+    ) -> bool {
+        let classes = || classes(s);
+        self.group.is_none_or(|g| s.group == g)
+            && self.section.is_none_or(|n| s.section == n)
+            && self.day.is_none_or(|d| classes().any(|c| c.day == d))
+            && self.from.is_none_or(|p| classes().all(|c| c.period.0 >= p))
+            && self.to.is_none_or(|p| classes().all(|c| c.period.1 <= p))
+            && self
+                .building
+                .is_none_or(|b| classes().any(|c| c.location.building() == b))
+            && (!self.has_seats || rem_seats(s.id) != Some(0))
+            && (!self.no_collisions || !collides(s.id))
+    }
+}
+
+fn classes(s: &Subject) -> impl Iterator<Item = &Class> {
+    [Some(&s.lec), s.tut.as_ref(), s.lab.as_ref()]
+        .into_iter()
+        .flatten()
+}
+
+/// `text` is lowercase
+fn prof_matches(s: &Subject, text: &str) -> bool {
+    classes(s).any(|c| match &c.ctype {
+        ClassType::Lecture { prof } => prof.to_lowercase().contains(text),
+        _ => false,
+    })
+}
+
+/// Returns a signal of the filter in the url query
+pub fn use_filter() -> Memo<SubjectFilter> {
+    let query_map = use_query_map();
+    Memo::new(move |_| query_map.with(SubjectFilter::from_query))
+}
+
+/// Filters the subjects of the accordion, the filters are kept in the url
+#[component]
+pub fn FilterBar(subjects: SubjectsSignal) -> impl IntoView {
+    use crate::utils::create_query_signal as query;
+
+    let filter = use_filter();
+    let (_, set_text) = query::<String>("q");
+    let (_, set_group) = query::<u8>("group");
+    let (_, set_section) = query::<u8>("section");
+    let (_, set_day) = query::<DayOfWeek>("day");
+    let (_, set_from) = query::<usize>("from");
+    let (_, set_to) = query::<usize>("to");
+    let (_, set_building) = query::<String>("building");
+    let (_, set_seats) = query::<bool>("seats");
+    let (_, set_no_collide) = query::<bool>("no_collide");
+
+    // a single navigation, setting every key would navigate once per key
+    let clear = {
+        let query_map = use_query_map();
+        let navigate = use_navigate();
+        let location = use_location();
+        move |_| {
+            let mut map = query_map.get_untracked();
+            for key in KEYS {
+                map.remove(key);
+            }
+            let path = location.pathname.get_untracked();
+            navigate(
+                &format!("{path}{}", map.to_query_string()),
+                NavigateOptions { replace: true, ..Default::default() },
+            );
+        }
+    };
+
+    let (groups, sections, buildings) = subjects.choices().with_value(|sc| {
+        let choices = sc.iter().flat_map(|c| &c.choices);
+        let groups: BTreeSet<_> = choices.clone().map(|s| s.group).collect();
+        let sections: BTreeSet<_> =
+            choices.clone().map(|s| s.section).collect();
+        let mut buildings = Vec::new();
+        for b in choices.flat_map(classes).map(|c| c.location.building()) {
+            if !buildings.contains(&b) {
+                buildings.push(b);
+            }
+        }
+        (groups, sections, buildings)
+    });
+
+    let non_empty = |v: String| (!v.is_empty()).then_some(v);
+    let periods = move |selected: fn(&SubjectFilter) -> Option<usize>| {
+        (1..=12)
+            .map(|p| {
+                view! {
+                    <option
+                        value=p
+                        selected=move || filter.with(|f| selected(f) == Some(p - 1))
+                    >
+                        {p}
+                    </option>
+                }
+            })
+            .collect_view()
+    };
+
+    view! {
+        <div class="mb-2 flex flex-wrap items-center gap-2 text-sm">
+            <label class="flex items-center gap-1 flex-grow">
+                {icon!("mdi/magnify", "text-indigo-300")}
+                <input
+                    type="search"
+                    class="flex-grow"
+                    placeholder="Code, name or professor"
+                    prop:value=move || filter.with(|f| f.text.clone())
+                    on:input=move |e| set_text(non_empty(event_target_value(&e)))
+                />
+            </label>
+            <select
+                aria-label="group"
+                on:change=move |e| set_group(event_target_value(&e).parse().ok())
+            >
+                <option value="">"Any group"</option>
+                {groups
+                    .into_iter()
+                    .map(|g| view! {
+                        <option value=g selected=move || filter.with(|f| f.group == Some(g))>
+                            {format!("Group {}", g)}
+                        </option>
+                    })
+                    .collect_view()}
+            </select>
+            <select
+                aria-label="section"
+                on:change=move |e| set_section(event_target_value(&e).parse().ok())
+            >
+                <option value="">"Any section"</option>
+                {sections
+                    .into_iter()
+                    .map(|s| view! {
+                        <option value=s selected=move || filter.with(|f| f.section == Some(s))>
+                            {format!("Section {}", s)}
+                        </option>
+                    })
+                    .collect_view()}
+            </select>
+            <select
+                aria-label="day"
+                on:change=move |e| set_day(event_target_value(&e).parse().ok())
+            >
+                <option value="">"Any day"</option>
+                {(0..6)
+                    .filter_map(DayOfWeek::from_repr)
+                    .map(|d| view! {
+                        <option value=d.to_string() selected=move || filter.with(|f| f.day == Some(d))>
+                            {d.to_string()}
+                        </option>
+                    })
+                    .collect_view()}
+            </select>
+            <label>
+                "Periods "
+                <select
+                    aria-label="from period"
+                    on:change=move |e| set_from(event_target_value(&e).parse().ok())
+                >
+                    <option value="">"–"</option>
+                    {periods(|f| f.from)}
+                </select>
+                " to "
+                <select
+                    aria-label="to period"
+                    on:change=move |e| set_to(event_target_value(&e).parse().ok())
+                >
+                    <option value="">"–"</option>
+                    {periods(|f| f.to)}
+                </select>
+            </label>
+            <select
+                aria-label="building"
+                on:change=move |e| set_building(non_empty(event_target_value(&e)))
+            >
+                <option value="">"Any building"</option>
+                {buildings
+                    .into_iter()
+                    .map(|b| {
+                        let key: &'static str = b.into();
+                        view! {
+                            <option
+                                value=key
+                                selected=move || filter.with(|f| f.building == Some(b))
+                            >
+                                {b.to_string()}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+            <label>
+                <input
+                    type="checkbox"
+                    prop:checked=move || filter.with(|f| f.has_seats)
+                    on:change=move |e| set_seats(event_target_checked(&e).then_some(true))
+                />
+                " Has seats"
+            </label>
+            <label>
+                <input
+                    type="checkbox"
+                    prop:checked=move || filter.with(|f| f.no_collisions)
+                    on:change=move |e| set_no_collide(event_target_checked(&e).then_some(true))
+                />
+                " Fits my selection"
+            </label>
+            <button
+                type="button"
+                class="btn-primary-outline"
+                disabled=move || filter.with(|f| *f == SubjectFilter::default())
+                on:click=clear
+            >
+                {icon!("mdi/filter-remove-outline", "inline-block align-middle")}
+                "Clear"
+            </button>
+        </div>
+    }
+}
+
+/// Applies the url filter to the subjects, see [`SubjectFilter::apply`]
+pub fn filtered(
+    subjects: SubjectsSignal,
+    filter: Memo<SubjectFilter>,
+    list: &[SubjectChoices],
+) -> Vec<SubjectChoices> {
+    let seats = expect_context::<Seats>();
+    filter.with(|f| {
+        let rem_seats = |id| {
+            // a selected choice holds a seat of its own
+            if subjects.is_selected_untracked(id) {
+                return None;
+            }
+            seats.with(|s| {
+                s.iter().find_map(|&(sid, rem)| (sid == id).then_some(rem))
+            })
+        };
+        let collides = |id| subjects.collides_with_selection(id);
+        list.iter()
+            .filter_map(|sc| f.apply(sc, rem_seats, collides))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::class::fixture::class;
+    use crate::class::{Section, WeekParity};
+    use crate::registration::LockReason;
+
+    fn subject(
+        id: i64,
+        group: u8,
+        prof: &str,
+        day: DayOfWeek,
+        st: usize,
+    ) -> Subject {
+        let lec = ClassType::Lecture { prof: prof.to_owned() };
+        let tut = ClassType::Tutorial {
+            sec_no: Section::One,
+            week_parity: WeekParity::Both,
+        };
+        Subject {
+            id: SubjectId(id),
+            group,
+            section: 1,
+            max_seats: 30,
+            lec: class(1, lec, day, (st, st + 1)),
+            tut: Some(class(1, tut, DayOfWeek::Saturday, (0, 1))),
+            lab: None,
+        }
+    }
+
+    fn ids(sc: Option<SubjectChoices>) -> Vec<i64> {
+        sc.map(|sc| sc.choices.iter().map(|s| s.id.0).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn apply() {
+        let sc = SubjectChoices {
+            level: 1,
+            name: "Data Structures".to_owned(),
+            code: "CSE131".to_owned(),
+            credit: 3,
+            choices: vec![
+                subject(1, 1, "Dr. Ahmed", DayOfWeek::Sunday, 0),
+                subject(2, 2, "Dr. Mona", DayOfWeek::Monday, 4),
+            ],
+            locked: Vec::new(),
+        };
+        let apply = |f: SubjectFilter| {
+            ids(f.apply(&sc, |id| (id.0 == 2).then_some(0), |id| id.0 == 1))
+        };
+        let text = |t: &str| SubjectFilter {
+            text: t.to_owned(),
+            ..Default::default()
+        };
+
+        assert_eq!(apply(SubjectFilter::default()), [1, 2]);
+        assert_eq!(apply(text(" cse1")), [1, 2]);
+        assert_eq!(apply(text("structures")), [1, 2]);
+        assert_eq!(apply(text("MONA")), [2]);
+        assert_eq!(apply(text("physics")), Vec::<i64>::new());
+
+        let day = Some(DayOfWeek::Monday);
+        assert_eq!(apply(SubjectFilter { day, ..Default::default() }), [2]);
+        // the tutorials are in the first two periods
+        let from = Some(2);
+        let none = Vec::<i64>::new();
+        assert_eq!(apply(SubjectFilter { from, ..Default::default() }), none);
+        let to = Some(1);
+        assert_eq!(apply(SubjectFilter { to, ..Default::default() }), [1]);
+        let building = Some(Building::Mechanics);
+        assert_eq!(
+            apply(SubjectFilter { building, ..Default::default() }),
+            [1, 2]
+        );
+        let group = Some(2);
+        assert_eq!(apply(SubjectFilter { group, ..Default::default() }), [2]);
+        let has_seats = true;
+        assert_eq!(
+            apply(SubjectFilter { has_seats, ..Default::default() }),
+            [1]
+        );
+        let no_collisions = true;
+        assert_eq!(
+            apply(SubjectFilter { no_collisions, ..Default::default() }),
+            [2]
+        );
+
+        let locked = SubjectChoices {
+            choices: Vec::new(),
+            locked: vec![LockReason::AlreadyCompleted],
+            ..sc.clone()
+        };
+        assert!(text("cse").apply(&locked, |_| None, |_| false).is_some());
+        let f = SubjectFilter { group, ..Default::default() };
+        assert!(f.apply(&locked, |_| None, |_| false).is_none());
+    }
+}
@@ -0,0 +1,288 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+use super::subjects_signal::{SaveState, SubjectsSignal};
+use super::SubjectId;
+use crate::components::suserr::TransErr;
+use crate::login::UserId;
+
+#[cfg(feature = "ssr")]
+use super::add_drop::Change;
+
+/// Events returned by a single query
+#[cfg(feature = "ssr")]
+const MAX_EVENTS: u32 = 1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "ssr",
+    derive(sqlx::Type),
+    sqlx(rename_all = "snake_case")
+)]
+pub enum EventAction {
+    Add,
+    Drop,
+    Swap,
+}
+
+/// A change to a student's registration, see `registration_events`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct RegistrationEvent {
+    pub id: i64,
+    pub student_id: UserId,
+    pub action: EventAction,
+    /// The choice added or dropped, the one swapped to for swaps
+    pub term_subject_id: SubjectId,
+    /// `CODE G# S#` of `term_subject_id`
+    pub subject: String,
+    pub swapped_from_id: Option<SubjectId>,
+    /// `G# S#` of `swapped_from_id`
+    pub swapped_from: Option<String>,
+    /// `None` for changes made by the system
+    pub actor_id: Option<UserId>,
+    pub actor: Option<String>,
+    /// UTC, `YYYY-MM-DD HH:MM:SS`
+    pub created_at: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Who made a change to a registration, and from where
+#[cfg(feature = "ssr")]
+#[derive(Clone, Default, Debug)]
+pub(crate) struct EventSource {
+    pub actor_id: Option<UserId>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub reason: Option<String>,
+}
+
+#[cfg(feature = "ssr")]
+impl EventSource {
+    pub fn from_request(
+        req: &actix_web::HttpRequest,
+        actor_id: UserId,
+    ) -> Self {
+        let user_agent = req
+            .headers()
+            .get(actix_web::http::header::USER_AGENT)
+            .and_then(|ua| ua.to_str().ok());
+        Self {
+            actor_id: Some(actor_id),
+            // the forwarded headers are set by the client, so can't be
+            // trusted for the record
+            ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+            user_agent: user_agent.map(Into::into),
+            reason: None,
+        }
+    }
+
+    /// A change made by the system on its own
+    pub fn system(reason: &str) -> Self {
+        Self { reason: Some(reason.into()), ..Default::default() }
+    }
+}
+
+/// Appends an event for each of `changes`, as part of the transaction
+/// applying them
+#[cfg(feature = "ssr")]
+pub(crate) async fn record_events(
+    conn: &mut sqlx::SqliteConnection,
+    student_id: UserId,
+    changes: &[Change],
+    source: &EventSource,
+) -> sqlx::Result<()> {
+    for change in changes {
+        let (action, id, swapped_from) = match *change {
+            Change::Add(id) => (EventAction::Add, id, None),
+            Change::Drop(id) => (EventAction::Drop, id, None),
+            Change::Swap { from, to } => (EventAction::Swap, to, Some(from)),
+        };
+        sqlx::query!(
+            r#"
+                INSERT INTO registration_events (
+                    student_id, term_subject_id, action, swapped_from,
+                    actor_id, ip, user_agent, reason
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            student_id,
+            id,
+            action,
+            swapped_from,
+            source.actor_id,
+            source.ip,
+            source.user_agent,
+            source.reason
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Filters of the events query, every one is optional
+#[cfg(feature = "ssr")]
+#[derive(Deserialize, Clone, Default, Debug)]
+pub struct EventFilter {
+    pub student: Option<UserId>,
+    /// Events of the choice, including swaps from it
+    pub subject: Option<SubjectId>,
+    /// Inclusive, `YYYY-MM-DD[ HH:MM:SS]` in UTC
+    pub since: Option<String>,
+    /// Exclusive, `YYYY-MM-DD[ HH:MM:SS]` in UTC
+    pub until: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// Returns the events matching `filter`, newest first
+#[cfg(feature = "ssr")]
+pub(crate) async fn registration_events(
+    pool: &sqlx::SqlitePool,
+    filter: &EventFilter,
+) -> sqlx::Result<Vec<RegistrationEvent>> {
+    let limit = filter.limit.map_or(MAX_EVENTS, |l| l.min(MAX_EVENTS));
+    sqlx::query_as!(
+        RegistrationEvent,
+        r#"
+            SELECT e.id,
+                   e.student_id AS "student_id: UserId",
+                   e.action AS "action: EventAction",
+                   e.term_subject_id AS "term_subject_id: SubjectId",
+                   s.code || ' G' || ts.group_no || ' S' || ts.sec_no
+                       AS "subject!: String",
+                   e.swapped_from AS "swapped_from_id?: SubjectId",
+                   'G' || fts.group_no || ' S' || fts.sec_no
+                       AS "swapped_from?: String",
+                   e.actor_id AS "actor_id?: UserId",
+                   a.name AS "actor?: String",
+                   e.created_at,
+                   e.ip,
+                   e.user_agent,
+                   e.reason
+            FROM registration_events AS e
+            INNER JOIN term_subjects AS ts ON ts.id = e.term_subject_id
+            INNER JOIN subjects AS s ON s.id = ts.subject_id
+            LEFT JOIN term_subjects AS fts ON fts.id = e.swapped_from
+            LEFT JOIN users AS a ON a.id = e.actor_id
+            WHERE (?1 IS NULL OR e.student_id = ?1)
+              AND (?2 IS NULL OR e.term_subject_id = ?2 OR e.swapped_from = ?2)
+              AND (?3 IS NULL OR e.created_at >= ?3)
+              AND (?4 IS NULL OR e.created_at < ?4)
+            ORDER BY e.id DESC
+            LIMIT ?5
+        "#,
+        filter.student,
+        filter.subject,
+        filter.since,
+        filter.until,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// `GET /api/registration/events`, the events matching the `EventFilter`
+/// in the query string. Admins only
+#[cfg(feature = "ssr")]
+pub async fn registration_events_handler(
+    req: actix_web::HttpRequest,
+    pool: actix_web::web::Data<sqlx::SqlitePool>,
+    filter: actix_web::web::Query<EventFilter>,
+) -> actix_web::Result<actix_web::HttpResponse> {
+    use actix_web::{error::ErrorInternalServerError, HttpResponse};
+
+    let Some(user_id) = crate::login::user_id_from_jwt(&req) else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    if !crate::login::is_admin(&pool, user_id)
+        .await
+        .map_err(ErrorInternalServerError)?
+    {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let events = registration_events(&pool, &filter)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+/// The logged in student's registration events, newest first
+#[server(encoding = "GetJson")]
+pub async fn get_registration_history(
+) -> Result<Vec<RegistrationEvent>, ServerFnError> {
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Err(ServerFnError::ServerError("Auth Error".into()));
+    };
+
+    let filter =
+        EventFilter { student: Some(student_id), ..Default::default() };
+    Ok(registration_events(&pool, &filter).await?)
+}
+
+/// Every add, drop and swap of the student's registration, refreshed on save
+#[component]
+pub fn RegistrationHistory(subjects: SubjectsSignal) -> impl IntoView {
+    let state = subjects.save_state();
+    let history = Resource::new(
+        move || state() == SaveState::Saved,
+        |_| get_registration_history(),
+    );
+
+    let row = |e: &RegistrationEvent| {
+        let change = match (e.action, &e.swapped_from) {
+            (EventAction::Add, _) => format!("Added {}", e.subject),
+            (EventAction::Drop, _) => format!("Dropped {}", e.subject),
+            (EventAction::Swap, Some(from)) => {
+                format!("Swapped {from} for {}", e.subject)
+            }
+            (EventAction::Swap, None) => format!("Swapped to {}", e.subject),
+        };
+        let by = match e.actor_id {
+            Some(id) if id == e.student_id => "You".to_owned(),
+            Some(_) => e.actor.clone().unwrap_or_default(),
+            None => "System".to_owned(),
+        };
+        view! {
+            <tr>
+                <td class="pr-4 whitespace-nowrap">{&e.created_at}</td>
+                <td class="pr-4">{change}</td>
+                <td class="pr-4">{by}</td>
+                <td class="opacity-70">{e.reason.clone()}</td>
+            </tr>
+        }
+    };
+
+    view! {
+        <details class="mb-2 p-2 border rounded">
+            <summary class="font-bold cursor-pointer">"Registration History"</summary>
+            <TransErr resource=history let:events>
+                {match events.is_empty() {
+                    true => view! { <p class="py-2 text-sm">"No changes yet"</p> }.into_view(),
+                    false => view! {
+                        <div class="max-h-64 overflow-y-auto">
+                            <table class="w-full text-sm text-left">
+                                <thead>
+                                    <tr>
+                                        <th>"Time (UTC)"</th>
+                                        <th>"Change"</th>
+                                        <th>"By"</th>
+                                        <th>"Reason"</th>
+                                    </tr>
+                                </thead>
+                                <tbody>{events.iter().map(row).collect_view()}</tbody>
+                            </table>
+                        </div>
+                    }.into_view(),
+                }}
+            </TransErr>
+        </details>
+    }
+}
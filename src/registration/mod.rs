@@ -1,34 +1,115 @@
+mod add_drop;
 mod class_card;
+mod credit_load;
+mod filter;
+mod history;
+#[cfg(feature = "ssr")]
+mod prereq;
 #[cfg(feature = "ssr")]
 pub mod rem_seats_ws;
 mod server_fns;
+mod solver;
+mod status_bar;
 mod subjects_signal;
+mod validation;
+mod waitlist;
+mod window;
+mod ws_protocol;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashSet};
 
 use leptos::*;
 use leptos_router::*;
-use leptos_use::{use_websocket, UseWebsocketReturn};
+use leptos_use::core::ConnectionReadyState;
+use leptos_use::{
+    use_websocket_with_options, UseWebSocketOptions, UseWebsocketReturn,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::class::Class;
 use crate::components::accordion::*;
+use crate::components::suserr::SusErr;
+use crate::icon;
 use crate::registration::class_card::ClassCard;
 use crate::timetable::{View, *};
-use subjects_signal::SubjectsSignal;
+use crate::utils::unix_now;
+use subjects_signal::{SaveState, SubjectsSignal};
+
+#[cfg(feature = "ssr")]
+pub use history::registration_events_handler;
 
 #[rustfmt::skip]
 #[derive(Serialize, PartialOrd, Ord, PartialEq, Eq, Deserialize, Copy, Clone, Hash, Debug)]
 #[cfg_attr(feature = "ssr", derive(sqlx::Type), sqlx(transparent))]
 pub struct SubjectId(i64);
 
+impl std::fmt::Display for SubjectId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 /// A collection of different choices for a specific subject
 #[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
 pub struct SubjectChoices {
     level: u8,
     name: String,
     code: String,
+    credit: u8,
+    /// Empty for locked subjects
     choices: Vec<Subject>,
+    /// Why the subject can't be registered, empty if it can
+    locked: Vec<LockReason>,
+}
+
+impl SubjectChoices {
+    /// Returns true if no choice of the subject can be registered or waitlisted
+    pub fn is_locked(&self) -> bool {
+        self.locked.iter().any(LockReason::blocks)
+    }
+}
+
+/// Why a subject can't be registered
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum LockReason {
+    /// Descriptions of the pre-requisites the student doesn't meet
+    MissingPrerequisites(Vec<String>),
+    /// The stored pre-requisites are malformed
+    InvalidPrerequisites,
+    AlreadyCompleted,
+    /// The subject is above the student's level
+    LevelTooHigh {
+        level: u8,
+        student_level: u32,
+    },
+    /// Every choice of the subject is full, a seat can still be waitlisted
+    NoSeats,
+}
+
+impl LockReason {
+    /// false if the subject can still be waitlisted
+    pub fn blocks(&self) -> bool {
+        *self != Self::NoSeats
+    }
+}
+
+impl std::fmt::Display for LockReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPrerequisites(missing) => {
+                write!(f, "Requires {}", missing.join("; "))
+            }
+            Self::InvalidPrerequisites => {
+                write!(f, "Pre-requisites unavailable, contact the registrar")
+            }
+            Self::AlreadyCompleted => write!(f, "Already completed"),
+            Self::LevelTooHigh { level, student_level } => write!(
+                f,
+                "Level {level} subject, you're at level {student_level}"
+            ),
+            Self::NoSeats => write!(f, "Every group is full"),
+        }
+    }
 }
 
 /// A container for a class and its associated sections and labs
@@ -36,6 +117,7 @@ pub struct SubjectChoices {
 pub struct Subject {
     id: SubjectId,
     group: u8,
+    section: u8,
     max_seats: u32,
     lec: Class,
     tut: Option<Class>,
@@ -47,10 +129,124 @@ pub type SelectedSubjectsResource = Resource<(), SelectedSubjects>;
 pub type AllSubjectsResource =
     Resource<(), Result<Vec<SubjectChoices>, ServerFnError>>;
 type Seats = Signal<Vec<(SubjectId, u32)>>;
+/// The student's position in every waitlist they're in, a newtype so it
+/// doesn't shadow `Seats` in the context
+#[derive(Clone, Copy)]
+struct Waitlist(Signal<Vec<(SubjectId, u32)>>);
+/// The student's registration window, kept up to date by the server
+#[derive(Clone, Copy)]
+struct WindowSignal(RwSignal<Option<window::RegistrationWindow>>);
+/// The state of the student's registration window, following the countdown
+#[derive(Clone, Copy)]
+struct WindowStateMemo(Memo<window::WindowState>);
 type TabRwSignal = (Memo<usize>, SignalSetter<usize>);
 
+/// Attempts to reconnect the live updates before giving up
+const RECONNECT_LIMIT: u64 = 10;
+/// Milliseconds between reconnection attempts
+const RECONNECT_INTERVAL: u64 = 3000;
+
 #[component]
 pub fn RegistrationPage() -> impl IntoView {
+    let window = Resource::new(|| (), |_| window::get_registration_window());
+
+    view! {
+        <SusErr resource=window let:window>
+            <RegistrationWindowGate window=window.clone()/>
+        </SusErr>
+    }
+}
+
+/// Shows the registration while the student's window accepts changes,
+/// and a countdown to its next state change
+#[component]
+fn RegistrationWindowGate(
+    window: Option<window::RegistrationWindow>,
+) -> impl IntoView {
+    use window::WindowState;
+
+    let now = RwSignal::new(unix_now());
+    create_effect(move |_| {
+        let handle = set_interval_with_handle(
+            move || now.set(unix_now()),
+            std::time::Duration::from_secs(1),
+        );
+        if let Ok(handle) = handle {
+            on_cleanup(move || handle.clear());
+        }
+    });
+
+    let term = window.as_ref().map(|w| w.term.clone());
+    let calendar = Resource::new(
+        || (),
+        move |_| crate::calendar::get_term_calendar(term.clone()),
+    );
+    let term_dates = move || {
+        let calendar = calendar.get()?.ok()??;
+        let t = calendar.term;
+        Some(format!("Classes run from {} to {}", t.starts_on, t.ends_on))
+    };
+
+    let window = RwSignal::new(window);
+    provide_context(WindowSignal(window));
+    let state = Memo::new(move |_| {
+        window.with(|w| {
+            w.as_ref().map_or(WindowState::Closed, |w| w.state(now()))
+        })
+    });
+    provide_context(WindowStateMemo(state));
+    let countdown = move || {
+        window.with(|w| {
+            let w = w.as_ref()?;
+            let rem = w.next_change(now())? - now();
+            let label = match state() {
+                WindowState::NotOpen => "Registration opens in",
+                WindowState::Open => "Registration closes in",
+                WindowState::AddDrop => "Add/drop ends in",
+                WindowState::Closed => return None,
+            };
+            Some(format!("{} - {label} {}", w.term, format_countdown(rem)))
+        })
+    };
+
+    view! {
+        <p class="px-4 py-1 text-sm text-indigo-500 dark:text-indigo-300">
+            {countdown}
+            <span class="block text-xs">{term_dates}</span>
+        </p>
+        {move || match state() {
+            WindowState::Open | WindowState::AddDrop => {
+                view! { <Registration/> }.into_view()
+            }
+            WindowState::NotOpen => ().into_view(),
+            WindowState::Closed => view! {
+                <p class="rounded-lg p-4 bg-secondary shadow-lg">
+                    "Registration is closed"
+                </p>
+            }
+            .into_view(),
+        }}
+    }
+}
+
+/// Formats a duration in seconds as `[Nd ]HH:MM:SS`
+fn format_countdown(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let hms = format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    match days {
+        0 => hms,
+        d => format!("{d}d {hms}"),
+    }
+}
+
+#[component]
+fn Registration() -> impl IntoView {
     // let all_subjects = Resource::new(|| (), |_| get_registerable_subjects());
     // let selected_subjects = Resource::new(|| (), |_| get_subbed_subjects());
     // TODO: handle errors with a modal or smth..
@@ -67,23 +263,69 @@ pub fn RegistrationPage() -> impl IntoView {
         )
     };
 
-    let rem_seats_ws = {
-        let UseWebsocketReturn { message, .. } = use_websocket("/ws/rem_seats");
-        move || {
-            message()
-                .and_then(|msg| serde_json::from_str(&msg).ok())
-                .unwrap_or_default()
+    // a choice to scroll to once its level's tab is shown
+    let revealing = RwSignal::new(None::<SubjectId>);
+
+    let rem_seats = RwSignal::new(Vec::<(SubjectId, u32)>::new());
+    let waitlist = RwSignal::new(Vec::new());
+    let notices = RwSignal::new(Vec::new());
+    let UseWebsocketReturn { ready_state, message, send, open, close, .. } =
+        use_websocket_with_options(
+            "/ws/rem_seats",
+            UseWebSocketOptions::default()
+                .reconnect_limit(RECONNECT_LIMIT)
+                .reconnect_interval(RECONNECT_INTERVAL),
+        );
+    create_effect({
+        let reg_window = expect_context::<WindowSignal>().0;
+        move |_| {
+            use ws_protocol::{DecodeError, Notice, NoticeLevel, ServerMsg};
+
+            let Some(msg) = message() else {
+                return;
+            };
+            match ws_protocol::decode(&msg) {
+                // the server only sends the seats that changed
+                Ok(ServerMsg::Seats(changed)) => rem_seats.update(|seats| {
+                    for (id, rem) in changed {
+                        match seats.iter_mut().find(|(s, _)| *s == id) {
+                            Some(seat) => seat.1 = rem,
+                            None => seats.push((id, rem)),
+                        }
+                    }
+                }),
+                Ok(ServerMsg::Waitlist(positions)) => waitlist.set(positions),
+                Ok(ServerMsg::Window(w)) => reg_window.set(w),
+                Ok(ServerMsg::Logout) => {
+                    let _ = leptos::window().location().set_href("/login");
+                }
+                Ok(ServerMsg::Notice(notice)) => {
+                    notices.update(|n| n.push(notice))
+                }
+                // the server was updated since the page was loaded
+                Err(DecodeError::Version) => {
+                    close();
+                    notices.update(|n| {
+                        n.push(Notice {
+                            level: NoticeLevel::Warning,
+                            text: "This page is out of date, reload it to get \
+                                   live updates"
+                                .into(),
+                        })
+                    });
+                }
+                Err(DecodeError::Malformed) => (),
+            }
         }
-    };
+    });
 
-    provide_context(rem_seats_ws.into_signal() as Seats);
+    provide_context(Seats::from(rem_seats));
+    provide_context(Waitlist(waitlist.into()));
 
-    // TODO: Make scrollable overflow
-    //       Hide extra data in a dropdown?
-    //       Add a filter bar (by group, section, ...)
-    //       Add and/or subtract from the rem_seats when selected
+    // TODO: Hide extra data in a dropdown?
     view! {
         <subjects_signal::CxtProvider let:subjects>
+            {subscribe_seats(subjects, tab_idx.0, ready_state, send.clone())}
             {move ||
                 subjects
                 .choices()
@@ -96,13 +338,22 @@ pub fn RegistrationPage() -> impl IntoView {
                     view! { <TabSelector tabs start_tab selector=tab_idx/> }
             })}
             <div class="rounded-b-lg p-4 bg-secondary shadow-lg">
+                <status_bar::Notices notices/>
+                {status_bar::connection_status(ready_state, open.clone())}
+                <filter::FilterBar subjects/>
                 <div class="flex flex-row items-stretch gap-2">
-                    <ClassAccordion curr_level=tab_idx.0 subjects/>
-                    <SideMenu/>
+                    <ClassAccordion curr_level=tab_idx.0 subjects revealing/>
+                    <SideMenu curr_level=tab_idx.0 subjects/>
                 </div>
                 // status + action bar
-                <div class="w-full py-2 flex gap-2 justify-end">
-                    // TODO: add status bar (collisions, selected credit hours...)
+                <div class="w-full py-2 flex gap-2 justify-end items-center">
+                    <status_bar::StatusBar
+                        curr_level=tab_idx.0
+                        select_tab=tab_idx.1
+                        subjects
+                        revealing
+                    />
+                    <status_bar::SaveStatus subjects/>
                     <button
                         type="button"
                         class="btn-primary-outline max-w-[1/6]"
@@ -114,12 +365,18 @@ pub fn RegistrationPage() -> impl IntoView {
                     <button
                         type="submit"
                         class="btn-primary max-w-[1/6]"
-                        disabled=subjects.saved()
+                        disabled={
+                            let saved = subjects.saved();
+                            let state = subjects.save_state();
+                            move || saved() || state() == SaveState::Pending
+                        }
                         on:click=move |_| subjects.save()
                     >
                         "Save"
                     </button>
                 </div>
+                <ScheduleGenerator subjects/>
+                <history::RegistrationHistory subjects/>
                 <TimetableGrid
                     data=subjects.classes()
                     flags=TimetableFlags {
@@ -128,6 +385,7 @@ pub fn RegistrationPage() -> impl IntoView {
                         show_prof: false,
                         show_code: true,
                         view: View::Grid,
+                        this_week_only: false,
                     }
                 />
             </div>
@@ -135,13 +393,76 @@ pub fn RegistrationPage() -> impl IntoView {
     }
 }
 
+/// Subscribes to the remaining seats of the shown choices, those of the
+/// current level matching the filters, as they change. They're subscribed to
+/// again on reconnection to resync them. The server sends the window and
+/// waitlist positions of a new connection on its own
+fn subscribe_seats(
+    subjects: SubjectsSignal,
+    curr_level: Memo<usize>,
+    ready_state: Signal<ConnectionReadyState>,
+    send: impl Fn(&str) + 'static,
+) {
+    use ws_protocol::{encode, ClientMsg};
+
+    let filter = filter::use_filter();
+    let shown = Memo::new(move |_| {
+        let level = curr_level() as u8;
+        // full choices are still watched, to show them once a seat frees up
+        let f = filter
+            .with(|f| filter::SubjectFilter { has_seats: false, ..f.clone() });
+        let collides = |id| subjects.collides_with_selection(id);
+        subjects.choices().with_value(|sc| {
+            sc.iter()
+                .filter(|c| c.level == level && !c.is_locked())
+                .filter_map(|c| f.apply(c, |_| None, &collides))
+                .flat_map(|c| c.choices.into_iter().map(|s| s.id))
+                .collect::<HashSet<_>>()
+        })
+    });
+
+    let subscribed = store_value(HashSet::new());
+    create_effect(move |_| {
+        // a new connection starts without any
+        if ready_state() != ConnectionReadyState::Open {
+            subscribed.set_value(HashSet::new());
+            return;
+        }
+        let shown = shown();
+        let (new, old): (Vec<_>, Vec<_>) = subscribed.with_value(|s| {
+            (
+                shown.difference(s).copied().collect(),
+                s.difference(&shown).copied().collect(),
+            )
+        });
+        if !old.is_empty() {
+            send(&encode(&ClientMsg::Unsubscribe(old)));
+        }
+        if !new.is_empty() {
+            send(&encode(&ClientMsg::Subscribe(new)));
+        }
+        subscribed.set_value(shown);
+    });
+}
+
 #[component]
 fn ClassAccordion(
     #[prop(into)] curr_level: Signal<usize>,
     subjects: SubjectsSignal,
+    revealing: RwSignal<Option<SubjectId>>,
 ) -> impl IntoView {
     // TODO: fix start_open
     fn row((_i, s): (usize, SubjectChoices)) -> leptos::View {
+        if s.is_locked() {
+            return view! { <LockedSubject subject=s/> }.into_view();
+        }
+        let full = s.locked.contains(&LockReason::NoSeats).then(|| {
+            view! {
+                <p class="col-span-full text-sm opacity-70">
+                    {LockReason::NoSeats.to_string()}
+                </p>
+            }
+        });
         view! {
             <AccordionItem
                 class="[&:has([data-selected])]:border-indigo-300 \
@@ -150,6 +471,7 @@ fn ClassAccordion(
                 inner_class="grid px-0.5 grid-cols-1 sm:grid-cols-2 md:grid-cols-3 lg:grid-cols-4 xl:grid-cols-5 2xl:grid-cols-6 gap-2"
                 head=move || view! { <span class="font-bold">{"["}{s.code}{"] "}{s.name}</span> }
             >
+                {full}
                 {s.choices
                     .into_iter()
                         .map(|subject| view! { <ClassCard subject/> })
@@ -157,19 +479,72 @@ fn ClassAccordion(
                 }
             </AccordionItem>
         }
+        .into_view()
     }
 
+    let filter = filter::use_filter();
+    let rows = move || {
+        let level: Vec<_> = subjects.choices().with_value(|s| {
+            s.iter()
+                .filter(|c| c.level == curr_level() as u8)
+                .cloned()
+                .collect()
+        });
+        let rows = filter::filtered(subjects, filter, &level);
+        if rows.is_empty() && !level.is_empty() {
+            return view! {
+                <p class="p-2 text-sm opacity-70">"No subject matches the filters"</p>
+            }
+            .into_view();
+        }
+        rows.into_iter().enumerate().map(row).collect_view()
+    };
+
+    let accordion = view! {
+        <div class="flex-grow max-h-[70vh] overflow-y-auto">
+            <Accordion>{rows}</Accordion>
+        </div>
+    };
+
+    // `rows` tracked the level first, so it's rendered by the time this runs
+    create_effect(move |_| {
+        let level = curr_level();
+        let Some(id) = revealing() else {
+            return;
+        };
+        if subjects
+            .describe(id)
+            .is_some_and(|(l, _)| l as usize == level)
+        {
+            revealing.set(None);
+            class_card::reveal(id);
+        }
+    });
+
+    accordion
+}
+
+/// A subject that can't be registered, along with the reasons why
+#[component]
+fn LockedSubject(subject: SubjectChoices) -> impl IntoView {
+    let reasons = subject
+        .locked
+        .iter()
+        .filter(|r| r.blocks())
+        .map(|r| view! { <li>{r.to_string()}</li> })
+        .collect_view();
+
     view! {
-        <Accordion>
-            {move || subjects.choices().with_value(|s| {
-                s.iter()
-                    .filter(|c| c.level == curr_level() as u8)
-                    .cloned()
-                    .enumerate()
-                    .map(row)
-                .collect_view()
-            })}
-        </Accordion>
+        <div
+            class="p-2 rounded border bg-gray-50 dark:bg-slate-900 opacity-60 cursor-not-allowed"
+            aria-disabled="true"
+        >
+            <span class="font-bold">
+                {icon!("mdi/lock-outline", "inline-block align-middle mr-1")}
+                {"["}{subject.code}{"] "}{subject.name}
+            </span>
+            <ul class="ml-6 text-sm list-disc">{reasons}</ul>
+        </div>
     }
 }
 
@@ -201,22 +576,84 @@ fn TabSelector(
     }
 }
 
+/// Applies a group/section preset to the subjects of the current level
 #[component]
-fn SideMenu() -> impl IntoView {
+fn SideMenu(
+    #[prop(into)] curr_level: Signal<usize>,
+    subjects: SubjectsSignal,
+) -> impl IntoView {
+    let seats = expect_context::<Seats>();
+    let group = RwSignal::new(None::<u8>);
+    let section = RwSignal::new(None::<u8>);
+    let report = RwSignal::new(None::<subjects_signal::PresetReport>);
+
+    // groups offered at the current level
+    let groups = Memo::new(move |_| {
+        let level = curr_level() as u8;
+        subjects.choices().with_value(|sc| {
+            sc.iter()
+                .filter(|c| c.level == level)
+                .flat_map(|c| c.choices.iter().map(|s| s.group))
+                .collect::<BTreeSet<_>>()
+        })
+    });
+    // the selection doesn't carry over to other levels
+    create_effect(move |_| {
+        let _ = curr_level();
+        group.set(None);
+        report.set(None);
+    });
+
+    let apply = move |_| {
+        let (Some(g), Some(s)) = (group(), section()) else {
+            return;
+        };
+        let is_full = |id| {
+            seats.with_untracked(|seats| {
+                seats.iter().any(|&(sid, rem)| sid == id && rem == 0)
+            })
+        };
+        let level = curr_level.get_untracked() as u8;
+        report.set(Some(subjects.apply_preset(level, g, s, is_full)));
+    };
+
+    let report_view = move || {
+        let report = report()?;
+        let missing = (!report.missing.is_empty()).then(|| {
+            format!("No matching choice: {}", report.missing.join(", "))
+        });
+        let full = (!report.full.is_empty())
+            .then(|| format!("Full: {}", report.full.join(", ")));
+        Some(view! {
+            <p class="w-full text-xs text-red-500">
+                {missing}
+                <span class="block">{full}</span>
+            </p>
+        })
+    };
+
     view! {
         <div class="p-2 w-[min-content] flex flex-wrap items-center content-center justify-between gap-4 border rounded">
             <div class="flex flex-wrap gap-4">
                 <span class="font-bold">"Apply a Preset Schedule:"</span>
                 <div>
-                    <select id="group_select" aria-label="group">
-                        <option value="" selected disabled>
+                    <select
+                        id="group_select"
+                        aria-label="group"
+                        on:change=move |e| group.set(event_target_value(&e).parse().ok())
+                    >
+                        <option value="" selected=move || group().is_none() disabled>
                             "Group…"
                         </option>
-                        {(1..=4)
-                            .map(|i| view! { <option value=i>{format!("Group {}", i)}</option> })
-                            .collect_view()}
+                        <For each=groups key=|g| *g let:g>
+                            <option value=g>{format!("Group {}", g)}</option>
+                        </For>
                     </select>
-                    <select id="section_select" aria-label="section">
+                    <select
+                        id="section_select"
+                        aria-label="section"
+                        on:change=move |e| section.set(event_target_value(&e).parse().ok())
+                    >
                         <option value="" selected disabled>
                             "Section…"
                         </option>
@@ -225,7 +662,296 @@ fn SideMenu() -> impl IntoView {
                     </select>
                 </div>
             </div>
-            <button type="button" class="btn-primary">"Apply Preset"</button>
+            <button
+                type="button"
+                class="btn-primary"
+                disabled=move || group().is_none() || section().is_none()
+                on:click=apply
+            >
+                "Apply Preset"
+            </button>
+            {report_view}
         </div>
     }
 }
+
+/// Schedules shown by the generator
+const MAX_SCHEDULES: usize = 5;
+
+/// Generates conflict-free schedules of the wanted subjects, which can be
+/// previewed and applied in one click
+#[component]
+fn ScheduleGenerator(subjects: SubjectsSignal) -> impl IntoView {
+    use crate::class::DayOfWeek;
+    use solver::{solve, Choice, Preferences, Schedule};
+
+    let seats = expect_context::<Seats>();
+    // indices into `subjects.choices()`, initially the selected subjects
+    let wanted = RwSignal::new(subjects.choices().with_value(|sc| {
+        sc.iter()
+            .enumerate()
+            .filter(|(_, c)| {
+                c.choices
+                    .iter()
+                    .any(|s| subjects.is_selected_untracked(s.id))
+            })
+            .map(|(i, _)| i)
+            .collect::<BTreeSet<_>>()
+    }));
+    let prefs = RwSignal::new(Preferences::default());
+    let schedules = RwSignal::new(None::<Vec<Schedule>>);
+    let preview = RwSignal::new(None::<usize>);
+
+    let generate = move |_| {
+        let wanted: Vec<Vec<Choice>> = subjects.choices().with_value(|sc| {
+            wanted.with_untracked(|w| {
+                w.iter()
+                    .map(|&i| sc[i].choices.iter().map(Choice::from).collect())
+                    .collect()
+            })
+        });
+        let rem_seats = |id| {
+            // a selected choice holds a seat of its own
+            if subjects.is_selected_untracked(id) {
+                return None;
+            }
+            seats.with_untracked(|s| {
+                s.iter().find_map(|&(sid, rem)| (sid == id).then_some(rem))
+            })
+        };
+        let result = prefs
+            .with_untracked(|p| solve(&wanted, p, rem_seats, MAX_SCHEDULES));
+        preview.set(None);
+        schedules.set(Some(result));
+    };
+
+    let classes_of = move |ids: &[SubjectId]| -> Vec<Class> {
+        subjects.choices().with_value(|sc| {
+            sc.iter()
+                .flat_map(|c| &c.choices)
+                .filter(|s| ids.contains(&s.id))
+                .flat_map(|s| {
+                    [Some(s.lec.clone()), s.tut.clone(), s.lab.clone()]
+                })
+                .flatten()
+                .collect()
+        })
+    };
+    let preview_classes = Signal::derive(move || {
+        schedules
+            .with(|s| {
+                let i = preview()?;
+                Some(classes_of(&s.as_ref()?.get(i)?.choices))
+            })
+            .unwrap_or_default()
+    });
+    let apply = move |i: usize| {
+        let ids = schedules.with_untracked(|s| {
+            s.as_ref().and_then(|s| s.get(i)).map(|s| s.choices.clone())
+        });
+        let Some(ids) = ids else {
+            return;
+        };
+        // the schedule replaces the selection, the solver didn't see the
+        // subjects left out of it
+        let selected: Vec<_> = subjects.choices().with_value(|sc| {
+            sc.iter()
+                .flat_map(|c| c.choices.iter().map(|s| s.id))
+                .filter(|&id| subjects.is_selected_untracked(id))
+                .collect()
+        });
+        batch(|| {
+            selected
+                .into_iter()
+                .filter(|id| !ids.contains(id))
+                .for_each(|id| subjects.deselect(id));
+            ids.into_iter().for_each(|id| subjects.select(id));
+        });
+    };
+
+    let subject_boxes = subjects.choices().with_value(|sc| {
+        sc.iter()
+            .enumerate()
+            .filter(|(_, c)| !c.is_locked())
+            .map(|(i, c)| {
+                let label = format!("[{}] {}", c.code, c.name);
+                view! {
+                    <label class="block">
+                        <input
+                            type="checkbox"
+                            prop:checked=move || wanted.with(|w| w.contains(&i))
+                            on:change=move |e| {
+                                wanted.update(|w| {
+                                    if event_target_checked(&e) {
+                                        w.insert(i);
+                                    } else {
+                                        w.remove(&i);
+                                    }
+                                })
+                            }
+                        />
+                        " "{label}
+                    </label>
+                }
+            })
+            .collect_view()
+    });
+    let day_boxes = (0..6)
+        .filter_map(DayOfWeek::from_repr)
+        .map(|day| {
+            view! {
+                <label>
+                    <input
+                        type="checkbox"
+                        prop:checked=move || {
+                            prefs.with(|p| p.free_days.contains(&day))
+                        }
+                        on:change=move |e| {
+                            prefs.update(|p| {
+                                p.free_days.retain(|&d| d != day);
+                                if event_target_checked(&e) {
+                                    p.free_days.push(day);
+                                }
+                            })
+                        }
+                    />
+                    " "{day.short_name()}
+                </label>
+            }
+        })
+        .collect_view();
+    let groups: BTreeSet<_> = subjects.choices().with_value(|sc| {
+        sc.iter()
+            .flat_map(|c| c.choices.iter().map(|s| s.group))
+            .collect()
+    });
+
+    let results = move || {
+        let schedules = schedules()?;
+        if schedules.is_empty() {
+            return Some(
+                view! {
+                    <p class="text-sm text-red-500">
+                        "No conflict-free schedule matches the preferences"
+                    </p>
+                }
+                .into_view(),
+            );
+        }
+        let rows = schedules
+            .into_iter()
+            .enumerate()
+            .map(|(i, s)| {
+                view! {
+                    <li class="flex gap-2 items-center">
+                        <span class="mr-auto">
+                            {format!("Option {} - penalty {}", i + 1, s.penalty)}
+                        </span>
+                        <button
+                            type="button"
+                            class="btn-primary-outline"
+                            on:click=move |_| preview.set(Some(i))
+                        >
+                            "Preview"
+                        </button>
+                        <button
+                            type="button"
+                            class="btn-primary"
+                            on:click=move |_| apply(i)
+                        >
+                            "Apply"
+                        </button>
+                    </li>
+                }
+            })
+            .collect_view();
+        Some(view! { <ol class="flex flex-col gap-1">{rows}</ol> }.into_view())
+    };
+
+    view! {
+        <details class="mb-2 p-2 border rounded">
+            <summary class="font-bold cursor-pointer">"Generate a Schedule"</summary>
+            <div class="flex flex-wrap gap-4 py-2 text-sm">
+                <fieldset class="max-h-48 overflow-y-auto">
+                    <legend class="font-bold">"Subjects"</legend>
+                    {subject_boxes}
+                </fieldset>
+                <fieldset class="flex flex-col gap-2">
+                    <legend class="font-bold">"Preferences"</legend>
+                    <label>
+                        "No classes before period "
+                        <select on:change=move |e| {
+                            let p = event_target_value(&e).parse().unwrap_or(1usize);
+                            prefs.update(|prefs| prefs.earliest_period = p - 1);
+                        }>
+                            {(1..=12).map(|p| view! { <option value=p>{p}</option> }).collect_view()}
+                        </select>
+                    </label>
+                    <div class="flex gap-2">"Free days: " {day_boxes}</div>
+                    <label>
+                        "Prefer "
+                        <select on:change=move |e| {
+                            let g = event_target_value(&e).parse().ok();
+                            prefs.update(|p| p.group = g);
+                        }>
+                            <option value="">"Any group"</option>
+                            {groups
+                                .into_iter()
+                                .map(|g| view! { <option value=g>{format!("Group {}", g)}</option> })
+                                .collect_view()}
+                        </select>
+                    </label>
+                    <label>
+                        <input
+                            type="checkbox"
+                            on:change=move |e| {
+                                prefs.update(|p| p.minimize_gaps = event_target_checked(&e))
+                            }
+                        />
+                        " Minimize gaps between classes"
+                    </label>
+                    <label>
+                        <input
+                            type="checkbox"
+                            on:change=move |e| {
+                                prefs.update(|p| p.only_with_seats = event_target_checked(&e))
+                            }
+                        />
+                        " Only choices with remaining seats"
+                    </label>
+                    <button type="button" class="btn-primary" on:click=generate>
+                        "Generate"
+                    </button>
+                </fieldset>
+                <div class="flex-grow">{results}</div>
+            </div>
+            <Show when=move || preview().is_some()>
+                <TimetableGrid
+                    data=preview_classes
+                    flags=TimetableFlags {
+                        time_style: TimeStyle::Numbers,
+                        show_loc: false,
+                        show_prof: false,
+                        show_code: true,
+                        view: View::Grid,
+                        this_week_only: false,
+                    }
+                />
+            </Show>
+        </details>
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::format_countdown;
+
+    #[test]
+    fn countdown() {
+        assert_eq!(format_countdown(0), "00:00:00");
+        assert_eq!(format_countdown(3661), "01:01:01");
+        assert_eq!(format_countdown(86399), "23:59:59");
+        assert_eq!(format_countdown(2 * 86400 + 59), "2d 00:00:59");
+        assert_eq!(format_countdown(-5), "00:00:00");
+    }
+}
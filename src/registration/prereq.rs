@@ -0,0 +1,297 @@
+#![cfg(feature = "ssr")]
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::login::UserId;
+
+/// The pre-requisites of a subject, stored as JSON in `subjects.pre_req_expr`
+/// e.g. `{"all": [{"passed": 1}, {"any": [{"co_req": 2}, {"credits": 60}]}]}`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PreReq {
+    /// The subject was passed
+    Passed(i64),
+    /// The subject was passed with at least `min_points` grade points
+    Grade {
+        subject: i64,
+        min_points: f64,
+    },
+    /// The subject was passed, or is taken in the same term
+    CoReq(i64),
+    /// At least this many credit hours were earned
+    Credits(u32),
+    /// The student is at least at this level
+    Level(u32),
+    All(Vec<PreReq>),
+    Any(Vec<PreReq>),
+}
+
+impl Default for PreReq {
+    fn default() -> Self {
+        Self::All(Vec::new())
+    }
+}
+
+/// The academic record pre-requisites are checked against
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// The best grade points of every passed subject, keyed by `subjects.id`.
+    /// Failed subjects can be retaken, so they're left out
+    pub passed: HashMap<i64, f64>,
+    pub earned_credits: u32,
+    /// Derived from `earned_credits`, see `student_levels`
+    pub level: u32,
+}
+
+/// The record of a student with no grades
+impl Default for Record {
+    fn default() -> Self {
+        Self { passed: HashMap::new(), earned_credits: 0, level: 1 }
+    }
+}
+
+/// The stored formats, `subjects.pre_req` is a plain array of subject ids
+/// that must all be passed
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Ids(Vec<i64>),
+    Expr(PreReq),
+}
+
+impl Record {
+    pub fn from_grades(grades: &crate::grades::Grades, level: u32) -> Self {
+        let mut record = Self {
+            earned_credits: grades.earned_credits,
+            level,
+            ..Default::default()
+        };
+        let subjects = grades.terms.iter().flat_map(|t| &t.subjects);
+        for s in subjects.filter(|s| s.passed) {
+            let best = record.passed.entry(s.subject_id).or_insert(s.gpa);
+            *best = best.max(s.gpa);
+        }
+        record
+    }
+}
+
+impl PreReq {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        Ok(match serde_json::from_str(json)? {
+            Stored::Ids(ids) => {
+                Self::All(ids.into_iter().map(Self::Passed).collect())
+            }
+            Stored::Expr(expr) => expr,
+        })
+    }
+
+    /// Describes every requirement of the expression, one per term of a
+    /// top level `All`. `codes` maps `subjects.id` to the subject code
+    pub fn describe(&self, codes: &HashMap<i64, String>) -> Vec<String> {
+        match self {
+            Self::All(reqs) => reqs
+                .iter()
+                .map(|r| r.describe_nested(codes, false))
+                .collect(),
+            req => vec![req.describe_nested(codes, false)],
+        }
+    }
+
+    fn describe_nested(
+        &self,
+        codes: &HashMap<i64, String>,
+        nested: bool,
+    ) -> String {
+        let code =
+            |s: &i64| codes.get(s).cloned().unwrap_or_else(|| s.to_string());
+        let join = |reqs: &[PreReq], sep: &str| {
+            let s: Vec<_> = reqs
+                .iter()
+                .map(|r| r.describe_nested(codes, true))
+                .collect();
+            match nested && reqs.len() > 1 {
+                true => format!("({})", s.join(sep)),
+                false => s.join(sep),
+            }
+        };
+        match self {
+            Self::Passed(s) => code(s),
+            Self::Grade { subject, min_points } => {
+                format!(
+                    "{} with at least {min_points:.1} grade points",
+                    code(subject)
+                )
+            }
+            Self::CoReq(s) => format!("{} (or taking it this term)", code(s)),
+            Self::Credits(c) => format!("{c} earned credit hours"),
+            Self::Level(l) => format!("level {l}"),
+            Self::All(reqs) => join(reqs, " and "),
+            Self::Any(reqs) => join(reqs, " or "),
+        }
+    }
+
+    /// Returns the part of the expression the student doesn't meet,
+    /// `None` if it's met. `taking` are the subjects taken this term
+    pub fn unmet(
+        &self,
+        record: &Record,
+        taking: &HashSet<i64>,
+    ) -> Option<Self> {
+        let unless = |met: bool| (!met).then(|| self.clone());
+        match self {
+            Self::Passed(s) => unless(record.passed.contains_key(s)),
+            Self::Grade { subject, min_points } => unless(
+                record.passed.get(subject).is_some_and(|p| p >= min_points),
+            ),
+            Self::CoReq(s) => {
+                unless(record.passed.contains_key(s) || taking.contains(s))
+            }
+            Self::Credits(c) => unless(record.earned_credits >= *c),
+            Self::Level(l) => unless(record.level >= *l),
+            Self::All(reqs) => {
+                let unmet: Vec<_> = reqs
+                    .iter()
+                    .filter_map(|r| r.unmet(record, taking))
+                    .collect();
+                (!unmet.is_empty()).then_some(Self::All(unmet))
+            }
+            Self::Any(reqs) => unless(
+                reqs.is_empty()
+                    || reqs.iter().any(|r| r.unmet(record, taking).is_none()),
+            ),
+        }
+    }
+}
+
+/// Returns the academic record of the student
+pub(crate) async fn student_record(
+    pool: &sqlx::SqlitePool,
+    student_id: UserId,
+) -> sqlx::Result<Record> {
+    let grades = crate::grades::student_grades(pool, student_id).await?;
+    let level = sqlx::query_scalar!(
+        r#"
+            SELECT sp.level AS "level: u32"
+            FROM users AS u
+            INNER JOIN student_profile AS sp ON sp.id = u.profile_id
+            WHERE u.id = ?
+        "#,
+        student_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or(1);
+
+    Ok(Record::from_grades(&grades, level))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stored_formats() {
+        let legacy = PreReq::from_json("[1, 2]").unwrap();
+        assert_eq!(
+            legacy,
+            PreReq::All(vec![PreReq::Passed(1), PreReq::Passed(2)])
+        );
+        assert_eq!(PreReq::from_json("[]").unwrap(), PreReq::default());
+        assert!(PreReq::from_json(r#"{"passed": "A"}"#).is_err());
+
+        let expr = r#"{"any": [{"grade": {"subject": 1, "min_points": 3.0}}, {"level": 3}]}"#;
+        let expr = PreReq::from_json(expr).unwrap();
+        let grade = PreReq::Grade { subject: 1, min_points: 3.0 };
+        assert_eq!(expr, PreReq::Any(vec![grade, PreReq::Level(3)]));
+    }
+
+    #[test]
+    fn evaluation() {
+        use PreReq::*;
+        let record = Record {
+            passed: HashMap::from([(1, 2.0)]),
+            earned_credits: 30,
+            level: 2,
+            ..Default::default()
+        };
+        let taking = HashSet::from([3]);
+        let codes = HashMap::from([(1, "A".to_owned()), (2, "B".to_owned())]);
+
+        let expr = All(vec![
+            Passed(1),
+            CoReq(3),
+            Any(vec![Passed(2), Grade { subject: 1, min_points: 3.0 }]),
+            Credits(30),
+            Level(3),
+        ]);
+        let unmet = expr.unmet(&record, &taking).unwrap();
+        assert_eq!(
+            unmet.describe(&codes),
+            [
+                "B or A with at least 3.0 grade points".to_owned(),
+                "level 3".to_owned()
+            ]
+        );
+        assert_eq!(CoReq(3).unmet(&record, &HashSet::new()), Some(CoReq(3)));
+        assert_eq!(
+            Any(vec![Level(1), Passed(2)]).unmet(&record, &taking),
+            None
+        );
+        assert_eq!(PreReq::default().unmet(&record, &taking), None);
+    }
+
+    #[test]
+    fn retakes() {
+        use crate::grades::{grades_from_attempts, Attempt, RepeatPolicy};
+        use crate::registration::credit_load::CreditLimits;
+        use crate::registration::validation::{validate, Candidate, Violation};
+        use crate::registration::SubjectId;
+        use std::collections::BTreeSet;
+
+        // graded before marks were stored, without a scale
+        let attempt = |term_no, gpa| Attempt {
+            subject_id: 1,
+            code: "A".to_owned(),
+            name: String::new(),
+            credit: 3,
+            term_no,
+            term_abs: String::new(),
+            completed_on: String::new(),
+            gpa,
+            marks: None,
+            by_law: 1,
+        };
+        let violations = |attempts| {
+            let grades = grades_from_attempts(
+                attempts,
+                RepeatPolicy::Best,
+                &HashMap::new(),
+            );
+            let candidate = Candidate {
+                id: SubjectId(1),
+                subject_id: 1,
+                credit: 3,
+                level: 1,
+                rem_seats: 1,
+                is_new: true,
+                is_registered: false,
+                pre_req: Some(PreReq::default()),
+                slots: Vec::new(),
+            };
+            validate(
+                &BTreeSet::from([SubjectId(1)]),
+                &[candidate],
+                &Record::from_grades(&grades, 1),
+                &HashMap::new(),
+                &CreditLimits::default(),
+            )
+        };
+
+        assert!(violations(vec![attempt(1, 0.0)]).is_empty());
+        assert_eq!(
+            violations(vec![attempt(1, 0.0), attempt(2, 3.0)]),
+            [Violation::AlreadyCompleted(SubjectId(1))]
+        );
+    }
+}
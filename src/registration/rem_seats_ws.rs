@@ -1,37 +1,173 @@
 #![cfg(feature = "ssr")]
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
 use actix::*;
 use actix_broker::BrokerSubscribe;
 use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_actors::ws;
 use serde::{Deserialize, Serialize};
 
+use super::ws_protocol::{self, ClientMsg, DecodeError, Notice, ServerMsg};
+use super::SubjectId;
+use crate::login::{Session, UserId};
+
 #[derive(Message, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
-pub struct RemSeatsMsg(pub Vec<(super::SubjectId, u32)>);
+pub struct RemSeatsMsg(pub Vec<(SubjectId, u32)>);
+
+/// Issued whenever waitlist queues change, only the positions of the
+/// affected students are read again
+#[derive(Message, Clone, Default)]
+#[rtype(result = "()")]
+pub struct WaitlistMsg {
+    /// Students that joined or left a queue
+    pub students: Vec<UserId>,
+    /// Choices whose queues changed, moving up everyone behind
+    pub choices: Vec<SubjectId>,
+}
+
+/// Issued when a session logs out, to close its connections in other tabs
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct LogoutMsg(pub Session);
+
+/// A notice for a single student
+#[derive(Message, Clone)]
+#[rtype(result = "()")]
+pub struct NoticeMsg {
+    pub student_id: UserId,
+    pub notice: Notice,
+}
 
 struct RemSeatsWs {
     db_pool: sqlx::SqlitePool,
+    session: Session,
+    /// The choices the client wants the remaining seats of
+    subscribed: HashSet<SubjectId>,
+    /// The last remaining seats sent to the client
+    seats: HashMap<SubjectId, u32>,
+    /// The last waitlist positions sent to the client, `None` until the
+    /// first are sent
+    waitlist: Option<Vec<(SubjectId, u32)>>,
 }
 
 impl RemSeatsWs {
-    pub fn new(db_pool: sqlx::SqlitePool) -> Self {
-        Self { db_pool }
+    pub fn new(db_pool: sqlx::SqlitePool, session: Session) -> Self {
+        Self {
+            db_pool,
+            session,
+            subscribed: HashSet::new(),
+            seats: HashMap::new(),
+            waitlist: None,
+        }
+    }
+
+    fn send(ctx: &mut <Self as Actor>::Context, msg: &ServerMsg) {
+        ctx.text(ws_protocol::encode(msg));
+    }
+
+    /// Sends the subscribed seats that changed since they were last sent
+    fn send_seats(
+        &mut self,
+        seats: Vec<(SubjectId, u32)>,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        let changed: Vec<_> = seats
+            .into_iter()
+            .filter(|(id, _)| self.subscribed.contains(id))
+            .filter(|&(id, rem)| self.seats.insert(id, rem) != Some(rem))
+            .collect();
+        if changed.is_empty() {
+            return;
+        }
+        Self::send(ctx, &ServerMsg::Seats(changed));
+    }
+
+    /// Sends the student's registration window, then again once its state
+    /// changes. It's read again each time, in case the period was edited
+    fn send_window(&mut self, ctx: &mut <Self as Actor>::Context) {
+        let pool = self.db_pool.clone();
+        let student_id = self.session.user_id;
+        let fut = async move {
+            super::window::registration_window(&pool, Some(student_id)).await
+        }
+        .into_actor(self)
+        .map(|window, _, ctx: &mut Self::Context| {
+            let Ok(window) = window else {
+                return;
+            };
+            let now = crate::utils::unix_now();
+            if let Some(next) = window.as_ref().and_then(|w| w.next_change(now))
+            {
+                let delay = Duration::from_secs((next - now).max(0) as u64);
+                ctx.run_later(delay, |act, ctx| act.send_window(ctx));
+            }
+            Self::send(ctx, &ServerMsg::Window(window));
+        });
+        ctx.spawn(fut);
+    }
+
+    fn subscribe(
+        &mut self,
+        ids: Vec<SubjectId>,
+        ctx: &mut <Self as Actor>::Context,
+    ) {
+        let new: Vec<_> = ids
+            .into_iter()
+            .filter(|&id| self.subscribed.insert(id))
+            .collect();
+        // `get_rem_seats` returns every choice for no ids
+        if new.is_empty() {
+            return;
+        }
+
+        // waits for the snapshot, so it can't overwrite a newer broadcast
+        let pool = self.db_pool.clone();
+        let fut =
+            async move { super::server_fns::get_rem_seats(&new, pool).await }
+                .into_actor(self)
+                .map(|seats, act: &mut Self, ctx: &mut Self::Context| {
+                    if let Ok(RemSeatsMsg(seats)) = seats {
+                        act.send_seats(seats, ctx);
+                    }
+                });
+        ctx.wait(fut);
+    }
+
+    fn unsubscribe(&mut self, ids: Vec<SubjectId>) {
+        for id in ids {
+            self.subscribed.remove(&id);
+            self.seats.remove(&id);
+        }
+    }
+
+    fn logout(ctx: &mut <Self as Actor>::Context) {
+        Self::send(ctx, &ServerMsg::Logout);
+        ctx.close(Some(ws::CloseCode::Policy.into()));
+        ctx.stop();
     }
 }
 
 impl Actor for RemSeatsWs {
     type Context = ws::WebsocketContext<Self>;
     fn started(&mut self, ctx: &mut Self::Context) {
-        // Subscribe to the broker channel
+        // Subscribe to the broker channels
         self.subscribe_system_async::<RemSeatsMsg>(ctx);
+        self.subscribe_system_async::<WaitlistMsg>(ctx);
+        self.subscribe_system_async::<LogoutMsg>(ctx);
+        self.subscribe_system_async::<NoticeMsg>(ctx);
+        ctx.notify(WaitlistMsg {
+            students: vec![self.session.user_id],
+            ..Default::default()
+        });
+        self.send_window(ctx);
 
-        let pool = self.db_pool.clone();
-        // sends rem_seats to client on startup
-        ctx.add_message_stream(futures::stream::once(async move {
-            super::server_fns::get_rem_seats(&[], pool)
-                .await
-                .unwrap_or(RemSeatsMsg(Vec::new()))
-        }));
+        let expires_in = self.session.expires_at - crate::utils::unix_now();
+        ctx.run_later(
+            Duration::from_secs(expires_in.max(0) as u64),
+            |_, ctx| Self::logout(ctx),
+        );
     }
 }
 
@@ -42,9 +178,56 @@ impl Handler<RemSeatsMsg> for RemSeatsWs {
         msg: RemSeatsMsg,
         ctx: &mut Self::Context,
     ) -> Self::Result {
-        ctx.text(
-            serde_json::to_string(&msg.0).expect("This should never fail"),
-        );
+        self.send_seats(msg.0, ctx);
+    }
+}
+
+impl Handler<WaitlistMsg> for RemSeatsWs {
+    type Result = ();
+    fn handle(&mut self, msg: WaitlistMsg, ctx: &mut Self::Context) {
+        let student_id = self.session.user_id;
+        let affected = msg.students.contains(&student_id)
+            || self.waitlist.as_ref().is_none_or(|positions| {
+                positions.iter().any(|(id, _)| msg.choices.contains(id))
+            });
+        if !affected {
+            return;
+        }
+        let pool = self.db_pool.clone();
+
+        // only sends the positions if they've changed
+        let fut = async move {
+            super::waitlist::get_waitlist_positions(student_id, &pool).await
+        }
+        .into_actor(self)
+        .map(|positions, act: &mut Self, ctx: &mut Self::Context| {
+            match positions {
+                Ok(positions) if act.waitlist.as_ref() != Some(&positions) => {
+                    Self::send(ctx, &ServerMsg::Waitlist(positions.clone()));
+                    act.waitlist = Some(positions);
+                }
+                _ => (),
+            }
+        });
+        ctx.spawn(fut);
+    }
+}
+
+impl Handler<LogoutMsg> for RemSeatsWs {
+    type Result = ();
+    fn handle(&mut self, msg: LogoutMsg, ctx: &mut Self::Context) {
+        if msg.0 == self.session {
+            Self::logout(ctx);
+        }
+    }
+}
+
+impl Handler<NoticeMsg> for RemSeatsWs {
+    type Result = ();
+    fn handle(&mut self, msg: NoticeMsg, ctx: &mut Self::Context) {
+        if msg.student_id == self.session.user_id {
+            Self::send(ctx, &ServerMsg::Notice(msg.notice));
+        }
     }
 }
 
@@ -57,6 +240,19 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for RemSeatsWs {
     ) {
         match msg {
             Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Text(text)) => {
+                match ws_protocol::decode::<ClientMsg>(&text) {
+                    Ok(ClientMsg::Subscribe(ids)) => self.subscribe(ids, ctx),
+                    Ok(ClientMsg::Unsubscribe(ids)) => self.unsubscribe(ids),
+                    // an outdated client, which is told to reload by the
+                    // version of the server's frames
+                    Err(DecodeError::Version) => {
+                        ctx.close(Some(ws::CloseCode::Unsupported.into()));
+                        ctx.stop();
+                    }
+                    Err(DecodeError::Malformed) => (),
+                }
+            }
             Ok(ws::Message::Close(reason)) => {
                 ctx.close(reason);
                 ctx.stop()
@@ -71,7 +267,19 @@ pub async fn rem_seats_ws(
     pool: web::Data<sqlx::SqlitePool>,
     stream: web::Payload,
 ) -> Result<HttpResponse, Error> {
-    // TODO: check for auth
-    // TODO: check if registration is active for student_id
-    ws::start(RemSeatsWs::new(pool.get_ref().clone()), &req, stream)
+    let Some(session) = crate::login::session_from_jwt(&req) else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let state = super::window::window_state(&pool, Some(session.user_id))
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    if !state.accepts_changes() {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    ws::start(
+        RemSeatsWs::new(pool.get_ref().clone(), session),
+        &req,
+        stream,
+    )
 }
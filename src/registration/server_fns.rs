@@ -4,7 +4,17 @@ use super::{SubjectChoices, SubjectId};
 use leptos::*;
 
 #[cfg(feature = "ssr")]
-use super::{rem_seats_ws::RemSeatsMsg, Subject};
+use {
+    super::{
+        credit_load::CreditLimits,
+        prereq::Record,
+        rem_seats_ws::RemSeatsMsg,
+        validation::{RegistrationError, Violation},
+        Subject,
+    },
+    crate::class::DayOfWeek,
+    crate::login::UserId,
+};
 
 #[cfg(feature = "ssr")]
 #[cached::proc_macro::cached(time = 1000, time_refresh, result)]
@@ -12,11 +22,12 @@ pub async fn subject_by_id(s: SubjectId) -> sqlx::Result<Option<Subject>> {
     use crate::class::*;
     let pool = crate::utils::extract_pool().await;
 
-    let (group, max_seats) = {
+    let (group, section, max_seats) = {
         let query = sqlx::query!(
             r#"
                 SELECT 
                     ts.group_no,
+                    ts.sec_no,
                     ts.max_seats
                 FROM term_subjects AS ts 
                 WHERE ts.id = ?
@@ -26,7 +37,11 @@ pub async fn subject_by_id(s: SubjectId) -> sqlx::Result<Option<Subject>> {
         .fetch_one(&pool)
         .await?;
 
-        (query.group_no as u8, query.max_seats as u32)
+        (
+            query.group_no as u8,
+            query.sec_no as u8,
+            query.max_seats as u32,
+        )
     };
 
     let Some(lec): Option<Class> = sqlx::query_as!(
@@ -75,7 +90,15 @@ pub async fn subject_by_id(s: SubjectId) -> sqlx::Result<Option<Subject>> {
     assert!(tut.is_none() || tut.as_ref().unwrap().ctype.is_tutorial());
     assert!(lab.is_none() || lab.as_ref().unwrap().ctype.is_lab());
 
-    Ok(Some(Subject { id: s, max_seats, group, lec, tut, lab }))
+    Ok(Some(Subject {
+        id: s,
+        max_seats,
+        group,
+        section,
+        lec,
+        tut,
+        lab,
+    }))
 }
 
 /// Returns the remaining seats for the given subjects
@@ -88,15 +111,11 @@ pub async fn get_rem_seats(
     let query = if !subjects.is_empty() {
         let query_str = format!(
             r#"
-                SELECT ts.id, 
-                    (ts.max_seats - COUNT(tsub.term_subject_id)) as rem_seats
+                SELECT ts.id, (ts.max_seats - ts.taken_seats) AS rem_seats
                 FROM term_subjects AS ts
-                LEFT JOIN term_subscribers AS tsub 
-                    ON ts.id = tsub.term_subject_id
-                WHERE ts.id IN (?{})
-                GROUP BY ts.id
+                WHERE ts.id IN ({})
             "#,
-            ", ?".repeat(subjects.len() - 1)
+            bind_params(subjects.len())
         );
         let mut query = sqlx::query_as(&query_str);
         for s in subjects {
@@ -106,12 +125,8 @@ pub async fn get_rem_seats(
     } else {
         sqlx::query_as(
             r#"
-                SELECT ts.id, 
-                    (ts.max_seats - COUNT(tsub.term_subject_id)) as rem_seats
+                SELECT ts.id, (ts.max_seats - ts.taken_seats) AS rem_seats
                 FROM term_subjects AS ts
-                LEFT JOIN term_subscribers AS tsub 
-                    ON ts.id = tsub.term_subject_id
-                GROUP BY ts.id
             "#,
         )
         .fetch_all(&pool)
@@ -121,88 +136,110 @@ pub async fn get_rem_seats(
     Ok(RemSeatsMsg(query?))
 }
 
-#[server]
-pub async fn register_subjects(
-    #[server(default)] new: BTreeSet<SubjectId>,
-) -> Result<(), ServerFnError> {
-    // TODO: Collision detection
-    //       Deduping
-    //       (preferably on DB):
-    //       Completion check
-    //       Pre-requirements check
-    //       Credit hrs total check
-    //       Remaining seats check
-
-    use actix_broker::{Broker, SystemBroker};
-
-    let req = expect_context::<actix_web::HttpRequest>();
-    let pool = crate::utils::extract_pool().await;
-
-    // TODO: move this to a middleware
-    let student_id = if let Some(uid) = crate::login::user_id_from_jwt(&req) {
-        uid
-    } else {
-        expect_context::<leptos_actix::ResponseOptions>()
-            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
-        return Ok(());
-    };
+/// Returns `n` comma separated bind params
+/// used because sqlx doesn't support variable length bind params
+#[cfg(feature = "ssr")]
+fn bind_params(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
 
-    let diff: Vec<_> = {
-        let prev = sqlx::query_scalar!(
-            r#"
-            SELECT tsub.term_subject_id AS "id: SubjectId"
-            FROM term_subscribers as tsub
-            WHERE tsub.student_id = ?
+/// Validates `new` as the whole registration of the student,
+/// `prev` being the choices they're currently registered in
+#[cfg(feature = "ssr")]
+pub(crate) async fn check_registration(
+    conn: &mut sqlx::SqliteConnection,
+    new: &BTreeSet<SubjectId>,
+    prev: &BTreeSet<SubjectId>,
+    limits: &CreditLimits,
+    record: &Record,
+) -> sqlx::Result<Vec<Violation>> {
+    use super::prereq::PreReq;
+    use super::validation::*;
+    use crate::class::{TimeSlot, WeekParity};
+    use std::collections::{HashMap, HashSet};
+
+    let candidates_str = format!(
+        r#"
+            SELECT ts.id,
+                   ts.subject_id,
+                   s.credit,
+                   s.level,
+                   COALESCE(s.pre_req_expr, s.pre_req),
+                   (ts.max_seats - ts.taken_seats) AS rem_seats
+            FROM term_subjects AS ts
+            INNER JOIN subjects AS s ON s.id = ts.subject_id
+            WHERE ts.id IN ({})
         "#,
-            student_id
-        )
-        .fetch_all(&pool)
-        .await?
-        .into_iter()
-        .collect();
-
-        new.symmetric_difference(&prev).copied().collect()
-    };
-
-    if diff.is_empty() {
-        return Ok(());
+        bind_params(new.len())
+    );
+    let mut query = sqlx::query_as(&candidates_str);
+    for s in new {
+        query = query.bind(s);
     }
+    let candidates: Vec<(SubjectId, i64, i64, i64, String, i64)> =
+        query.fetch_all(&mut *conn).await?;
 
-    let mut tx = pool.begin().await?;
-
-    sqlx::query("DELETE FROM term_subscribers WHERE student_id = ?")
-        .bind(student_id)
-        .execute(&mut *tx)
-        .await?;
-
-    if !new.is_empty() {
-        // Insert into term_subscribers
-        // using string formatting because sqlx doesn't support variable length bind params
-        let query_str = format!(
-            r#"
-            INSERT INTO term_subscribers (student_id, term_subject_id) 
-            SELECT ?, sid.* 
-            FROM (VALUES (?){}) AS sid
-            "#,
-            ", (?)".repeat(new.len() - 1)
-        );
-
-        let mut query = sqlx::query(&query_str);
-        query = query.bind(student_id);
-        for s in &new {
-            query = query.bind(s);
-        }
+    let registered_str = format!(
+        "SELECT subject_id FROM term_subjects WHERE id IN ({})",
+        bind_params(prev.len())
+    );
+    let mut query = sqlx::query_scalar(&registered_str);
+    for s in prev {
+        query = query.bind(s);
+    }
+    let registered: HashSet<i64> =
+        query.fetch_all(&mut *conn).await?.into_iter().collect();
 
-        query.execute(&mut *tx).await?;
+    let mut slots: HashMap<SubjectId, Vec<TimeSlot>> = HashMap::new();
+    let slots_str = format!(
+        r#"
+            SELECT ts.id, c.day_of_week, c.period_start, c.period_end,
+                   IIF(c.type = 'lec', 'both', c.week_parity)
+            FROM term_subjects AS ts
+            INNER JOIN classes AS c
+                ON c.id IN (ts.lec_id, ts.tut_id, ts.lab_id)
+            WHERE ts.id IN ({})
+        "#,
+        bind_params(new.len())
+    );
+    let mut query = sqlx::query_as(&slots_str);
+    for s in new {
+        query = query.bind(s);
+    }
+    let slot_rows: Vec<(SubjectId, DayOfWeek, i64, i64, WeekParity)> =
+        query.fetch_all(&mut *conn).await?;
+    for (id, day, st, end, week_parity) in slot_rows {
+        let period = (st as usize, end as usize);
+        let slot = TimeSlot { day, period, week_parity };
+        slots.entry(id).or_default().push(slot);
     }
 
-    tx.commit().await?;
+    let codes: HashMap<i64, String> =
+        sqlx::query!("SELECT id, code FROM subjects")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .map(|r| (r.id, r.code))
+            .collect();
 
-    // Broadcast `rem_seats` changes to ws actors
-    Broker::<SystemBroker>::issue_async(
-        get_rem_seats(&diff, pool.clone()).await?,
-    );
-    Ok(())
+    let candidates: Vec<_> = candidates
+        .into_iter()
+        .map(
+            |(id, subject_id, credit, level, pre_req, rem_seats)| Candidate {
+                id,
+                subject_id,
+                credit: credit as u32,
+                level: level as u32,
+                rem_seats,
+                is_new: !prev.contains(&id),
+                is_registered: registered.contains(&subject_id),
+                pre_req: PreReq::from_json(&pre_req).ok(),
+                slots: slots.remove(&id).unwrap_or_default(),
+            },
+        )
+        .collect();
+
+    Ok(validate(new, &candidates, record, &codes, limits))
 }
 
 #[server(encoding = "GetJson")]
@@ -219,6 +256,20 @@ pub async fn get_subbed_subjects() -> Result<BTreeSet<SubjectId>, ServerFnError>
         return Ok(BTreeSet::new());
     };
 
+    let query = registered_choices(&pool, student_id).await?;
+
+    #[cfg(debug_assertions)]
+    println!("Subbed subjects: {:?}", &query);
+
+    Ok(query)
+}
+
+/// Returns the choices the student is registered in
+#[cfg(feature = "ssr")]
+async fn registered_choices(
+    pool: &sqlx::SqlitePool,
+    student_id: crate::login::UserId,
+) -> sqlx::Result<BTreeSet<SubjectId>> {
     let query = sqlx::query_scalar!(
         r#"
             SELECT ts.id AS "id: SubjectId" 
@@ -229,65 +280,127 @@ pub async fn get_subbed_subjects() -> Result<BTreeSet<SubjectId>, ServerFnError>
             "#,
         student_id
     )
-    .fetch_all(&pool)
+    .fetch_all(pool)
     .await?;
-
-    #[cfg(debug_assertions)]
-    println!("Subbed subjects: {:?}", &query);
-
     Ok(BTreeSet::from_iter(query))
 }
 
+/// Returns the subjects offered this term, with the choices of the ones the
+/// student can register. Locked subjects are only included if `include_locked`
 #[server(encoding = "GetJson")]
 pub async fn get_registerable_subjects(
+    #[server(default)] include_locked: bool,
 ) -> Result<Vec<SubjectChoices>, ServerFnError> {
+    use super::prereq::{student_record, PreReq};
+    use super::window::window_state;
+    use super::LockReason;
     use futures::{stream, StreamExt, TryStreamExt};
+    use std::collections::{HashMap, HashSet};
 
     let req = expect_context::<actix_web::HttpRequest>();
     let pool = crate::utils::extract_pool().await;
 
+    // anyone can browse the offered subjects, only students are bound by
+    // their registration window
     let student_id = crate::login::user_id_from_jwt(&req);
-    // TODO: check if registration is active for student_id
+    if let Some(id) = student_id {
+        if !window_state(&pool, Some(id)).await?.accepts_changes() {
+            expect_context::<leptos_actix::ResponseOptions>()
+                .set_status(actix_web::http::StatusCode::FORBIDDEN);
+            return Err(ServerFnError::ServerError(
+                RegistrationError::Closed.to_string(),
+            ));
+        }
+    }
 
     let subjects_by_id = sqlx::query!(
         r#"
-            SELECT s.level AS "level: u8",
+            SELECT s.id,
+                   s.level AS "level: u8",
                    s.name,
                    s.code,
+                   s.credit AS "credit: u8",
+                   COALESCE(s.pre_req_expr, s.pre_req) AS "pre_req!: String",
+                   MAX(ts.max_seats - ts.taken_seats) AS "rem_seats!: i64",
                    json_group_array(ts.id) AS "choices!: sqlx::types::Json<Vec<SubjectId>>"
             FROM subjects AS s
             INNER JOIN term_subjects AS ts ON ts.subject_id = s.id
-            LEFT JOIN completed AS c ON s.id = c.subject_id AND c.student_id = ?1
-            WHERE c.student_id IS NULL
-              AND NOT EXISTS (
-                SELECT value
-                FROM json_each(s.pre_req)
-                WHERE NOT EXISTS (
-                  SELECT *
-                  FROM completed AS c2
-                  WHERE c2.student_id = ?1
-                    AND c2.subject_id = value
-                )
-              )
             GROUP BY s.id
             ORDER By s.level, s.name;
-        "#, student_id)
+        "#)
         .fetch_all(&pool)
         .await?;
 
+    let (record, registered) = match student_id {
+        Some(id) => (
+            student_record(&pool, id).await?,
+            registered_choices(&pool, id).await?,
+        ),
+        None => (Record::default(), BTreeSet::new()),
+    };
+    let codes: HashMap<i64, String> =
+        sqlx::query!("SELECT id, code FROM subjects")
+            .fetch_all(&pool)
+            .await?
+            .into_iter()
+            .map(|r| (r.id, r.code))
+            .collect();
+    // co-requisites offered this term can be taken alongside
+    let offered: HashSet<i64> = subjects_by_id.iter().map(|s| s.id).collect();
+
+    let subjects_by_id = subjects_by_id.into_iter().filter_map(|s| {
+        let mut locked = Vec::new();
+        // the student can always drop or swap what they're registered in,
+        // even if they're no longer eligible for it
+        let is_registered = s.choices.iter().any(|id| registered.contains(id));
+        if !is_registered {
+            match PreReq::from_json(&s.pre_req).ok() {
+                Some(pre_req) => {
+                    if let Some(unmet) = pre_req.unmet(&record, &offered) {
+                        let missing = unmet.describe(&codes);
+                        locked.push(LockReason::MissingPrerequisites(missing));
+                    }
+                }
+                None => locked.push(LockReason::InvalidPrerequisites),
+            }
+            if record.passed.contains_key(&s.id) {
+                locked.push(LockReason::AlreadyCompleted);
+            }
+            if s.level as u32 > record.level {
+                locked.push(LockReason::LevelTooHigh {
+                    level: s.level,
+                    student_level: record.level,
+                });
+            }
+        }
+        if s.rem_seats <= 0 {
+            locked.push(LockReason::NoSeats);
+        }
+
+        let is_locked = locked.iter().any(LockReason::blocks);
+        (include_locked || !is_locked).then_some((s, locked, is_locked))
+    });
+
     let subjects = stream::iter(subjects_by_id)
-        .map(|s| async move {
-            let choices = stream::iter(s.choices.iter())
-                .map(|&s| subject_by_id(s))
-                .buffer_unordered(4)
-                .try_filter_map(|s| async move { Ok(s) })
-                .try_collect()
-                .await?;
+        .map(|(s, locked, is_locked)| async move {
+            let choices = match is_locked {
+                true => Vec::new(),
+                false => {
+                    stream::iter(s.choices.iter())
+                        .map(|&s| subject_by_id(s))
+                        .buffer_unordered(4)
+                        .try_filter_map(|s| async move { Ok(s) })
+                        .try_collect()
+                        .await?
+                }
+            };
             Ok(SubjectChoices {
                 level: s.level,
                 name: s.name,
                 code: s.code,
+                credit: s.credit,
                 choices,
+                locked,
             }) as Result<_, sqlx::Error>
         })
         .buffer_unordered(4)
@@ -299,6 +412,3 @@ pub async fn get_registerable_subjects(
 
     Ok(subjects)
 }
-
-#[cfg(test)]
-mod test {}
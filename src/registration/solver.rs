@@ -0,0 +1,300 @@
+use super::{Subject, SubjectId};
+use crate::class::{DayOfWeek, TimeSlot, WeekParity};
+
+/// Nodes visited before the search gives up on finding better schedules
+const SEARCH_LIMIT: usize = 100_000;
+/// Penalty of a choice outside the preferred group, worth that many idle periods
+const GROUP_PENALTY: u32 = 4;
+
+/// What the student wants from a generated schedule
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Preferences {
+    /// Classes can't start before this period, 0-indexed
+    pub earliest_period: usize,
+    /// Days without any classes
+    pub free_days: Vec<DayOfWeek>,
+    /// Choices of this group are preferred
+    pub group: Option<u8>,
+    /// Prefer schedules with fewer idle periods between the classes of a day
+    pub minimize_gaps: bool,
+    /// Skip full choices
+    pub only_with_seats: bool,
+}
+
+/// A conflict-free selection of one choice per wanted subject
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Schedule {
+    pub choices: Vec<SubjectId>,
+    /// Lower is better
+    pub penalty: u32,
+}
+
+/// A choice of a subject, reduced to what the solver needs
+#[derive(Clone, PartialEq, Eq)]
+pub struct Choice {
+    pub id: SubjectId,
+    pub group: u8,
+    pub slots: Vec<TimeSlot>,
+}
+
+impl From<&Subject> for Choice {
+    fn from(s: &Subject) -> Self {
+        let slots = [Some(&s.lec), s.tut.as_ref(), s.lab.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|c| c.time_slot())
+            .collect();
+        Self { id: s.id, group: s.group, slots }
+    }
+}
+
+/// Returns up to `max_results` conflict-free schedules picking one of the
+/// choices of every wanted subject, best first.
+/// `rem_seats` returns the remaining seats of a choice, if known
+pub fn solve(
+    wanted: &[Vec<Choice>],
+    prefs: &Preferences,
+    rem_seats: impl Fn(SubjectId) -> Option<u32>,
+    max_results: usize,
+) -> Vec<Schedule> {
+    let mut options: Vec<Vec<_>> = wanted
+        .iter()
+        .map(|choices| {
+            choices
+                .iter()
+                .filter(|c| {
+                    !prefs.only_with_seats || rem_seats(c.id) != Some(0)
+                })
+                .filter(|c| {
+                    c.slots.iter().all(|t| {
+                        t.period.0 >= prefs.earliest_period
+                            && !prefs.free_days.contains(&t.day)
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    if max_results == 0 || options.iter().any(|o| o.is_empty()) {
+        return Vec::new();
+    }
+
+    // the most constrained subjects first prune the search the earliest
+    options.sort_by_key(|o| o.len());
+
+    let mut search = Search {
+        options: &options,
+        prefs,
+        max_results,
+        visited: 0,
+        picked: Vec::with_capacity(options.len()),
+        results: Vec::new(),
+    };
+    search.run(0, 0);
+
+    let mut results = search.results;
+    for r in &mut results {
+        r.choices.sort();
+    }
+    results
+}
+
+struct Search<'a> {
+    /// the allowed choices of every wanted subject
+    options: &'a [Vec<&'a Choice>],
+    prefs: &'a Preferences,
+    max_results: usize,
+    visited: usize,
+    picked: Vec<&'a Choice>,
+    /// sorted by penalty
+    results: Vec<Schedule>,
+}
+
+impl Search<'_> {
+    fn run(&mut self, depth: usize, group_penalty: u32) {
+        self.visited += 1;
+        if self.visited > SEARCH_LIMIT {
+            return;
+        }
+        // the group penalty only grows deeper in the search
+        if self.results.len() == self.max_results
+            && self
+                .results
+                .last()
+                .is_some_and(|r| r.penalty <= group_penalty)
+        {
+            return;
+        }
+
+        let Some(options) = self.options.get(depth) else {
+            let penalty = group_penalty
+                + if self.prefs.minimize_gaps {
+                    gaps(self.picked.iter().flat_map(|c| c.slots.iter()))
+                } else {
+                    0
+                };
+            self.push(Schedule {
+                choices: self.picked.iter().map(|c| c.id).collect(),
+                penalty,
+            });
+            return;
+        };
+
+        for &choice in options {
+            let collides = self.picked.iter().any(|picked| {
+                choice
+                    .slots
+                    .iter()
+                    .any(|a| picked.slots.iter().any(|b| a.overlaps(b)))
+            });
+            if collides {
+                continue;
+            }
+
+            let penalty = match self.prefs.group {
+                Some(g) if g != choice.group => GROUP_PENALTY,
+                _ => 0,
+            };
+            self.picked.push(choice);
+            self.run(depth + 1, group_penalty + penalty);
+            self.picked.pop();
+        }
+    }
+
+    fn push(&mut self, schedule: Schedule) {
+        let idx = self
+            .results
+            .partition_point(|r| r.penalty <= schedule.penalty);
+        if idx < self.max_results {
+            self.results.insert(idx, schedule);
+            self.results.truncate(self.max_results);
+        }
+    }
+}
+
+/// Idle periods between the first and last class of every day,
+/// counted separately for odd and even weeks
+fn gaps<'a>(slots: impl Iterator<Item = &'a TimeSlot> + Clone) -> u32 {
+    let mut total = 0;
+    for week in [WeekParity::Odd, WeekParity::Even] {
+        // a bit per period of every day
+        let mut days = [0u32; 7];
+        for s in slots.clone().filter(|s| s.week_parity.overlaps(&week)) {
+            for p in s.period.0..=s.period.1 {
+                days[s.day as usize] |= 1 << p;
+            }
+        }
+        total += days
+            .iter()
+            .filter(|&&d| d != 0)
+            .map(|&d| {
+                let span = 32 - d.leading_zeros() - d.trailing_zeros();
+                span - d.count_ones()
+            })
+            .sum::<u32>();
+    }
+    total
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn choice(id: i64, group: u8, day: DayOfWeek, st: usize) -> Choice {
+        let slot = TimeSlot {
+            day,
+            period: (st, st + 1),
+            week_parity: WeekParity::Both,
+        };
+        Choice { id: SubjectId(id), group, slots: vec![slot] }
+    }
+
+    fn ids(schedule: &Schedule) -> Vec<i64> {
+        schedule.choices.iter().map(|s| s.0).collect()
+    }
+
+    #[test]
+    fn conflict_free() {
+        use DayOfWeek::*;
+        let wanted = [
+            vec![choice(1, 1, Sunday, 0), choice(2, 2, Monday, 0)],
+            vec![choice(3, 1, Sunday, 1), choice(4, 2, Sunday, 2)],
+        ];
+        let prefs = Preferences::default();
+        let mut all: Vec<_> = solve(&wanted, &prefs, |_| None, 10)
+            .iter()
+            .map(ids)
+            .collect();
+        all.sort();
+        assert_eq!(all, [vec![1, 4], vec![2, 3], vec![2, 4]]);
+
+        // the only choice of the second subject collides with the first
+        let wanted =
+            [vec![choice(1, 1, Sunday, 0)], vec![choice(2, 1, Sunday, 1)]];
+        assert!(solve(&wanted, &prefs, |_| None, 10).is_empty());
+    }
+
+    #[test]
+    fn preferences() {
+        use DayOfWeek::*;
+        let wanted = [
+            vec![choice(1, 1, Sunday, 0), choice(2, 2, Monday, 4)],
+            vec![choice(3, 1, Sunday, 4), choice(4, 2, Monday, 2)],
+        ];
+        let best = |prefs: Preferences| {
+            let seats = |id: SubjectId| (id.0 == 4).then_some(0);
+            solve(&wanted, &prefs, seats, 1).first().map(ids)
+        };
+
+        let free_days = vec![Monday];
+        let prefs = Preferences { free_days, ..Default::default() };
+        assert_eq!(best(prefs), Some(vec![1, 3]));
+
+        let prefs = Preferences { earliest_period: 3, ..Default::default() };
+        assert_eq!(best(prefs), Some(vec![2, 3]));
+
+        let prefs = Preferences { group: Some(2), ..Default::default() };
+        assert_eq!(best(prefs), Some(vec![2, 4]));
+
+        let prefs = Preferences {
+            group: Some(2),
+            only_with_seats: true,
+            ..Default::default()
+        };
+        assert_eq!(best(prefs), Some(vec![2, 3]));
+
+        let prefs = Preferences {
+            free_days: vec![Monday],
+            earliest_period: 1,
+            ..Default::default()
+        };
+        assert_eq!(best(prefs), None);
+    }
+
+    #[test]
+    fn minimize_gaps() {
+        use DayOfWeek::*;
+        let wanted = [
+            vec![choice(1, 1, Sunday, 0)],
+            vec![choice(2, 1, Sunday, 4), choice(3, 1, Sunday, 2)],
+        ];
+        let prefs = Preferences { minimize_gaps: true, ..Default::default() };
+        let all = solve(&wanted, &prefs, |_| None, 10);
+        let all: Vec<_> = all.iter().map(|s| (ids(s), s.penalty)).collect();
+        // 2 idle periods between 1 and 2, on both odd and even weeks
+        assert_eq!(all, [(vec![1, 3], 0), (vec![1, 2], 4)]);
+    }
+
+    #[test]
+    fn idle_periods() {
+        use DayOfWeek::*;
+        let slot =
+            |day, period, week_parity| TimeSlot { day, period, week_parity };
+        let slots = [
+            slot(Sunday, (0, 1), WeekParity::Both),
+            slot(Sunday, (4, 5), WeekParity::Odd),
+            slot(Monday, (3, 3), WeekParity::Both),
+        ];
+        // 2 idle periods on odd weeks only
+        assert_eq!(gaps(slots.iter()), 2);
+    }
+}
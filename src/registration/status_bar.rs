@@ -0,0 +1,271 @@
+use leptos::*;
+use leptos_use::core::ConnectionReadyState;
+
+use super::class_card::reveal;
+use super::credit_load::{get_credit_limits, Standing};
+use super::subjects_signal::{SaveState, SubjectsSignal};
+use super::validation::RegistrationError;
+use super::ws_protocol::{Notice, NoticeLevel};
+use super::SubjectId;
+use crate::components::suserr::SusErr;
+use crate::icon;
+
+/// A live summary of the selection
+#[component]
+pub fn StatusBar(
+    #[prop(into)] curr_level: Signal<usize>,
+    select_tab: SignalSetter<usize>,
+    subjects: SubjectsSignal,
+    /// Set to a choice of another level, revealed once the level is shown
+    revealing: RwSignal<Option<SubjectId>>,
+) -> impl IntoView {
+    let count = subjects.selected_count();
+    let collisions = subjects.collisions();
+    let diff = subjects.diff();
+    let missing = move || subjects.missing_choice(curr_level() as u8);
+
+    // switches to the level of the choice first, if needed
+    let show = move |id: SubjectId| {
+        let Some((level, _)) = subjects.describe(id) else {
+            return;
+        };
+        if level as usize == curr_level.get_untracked() {
+            reveal(id);
+        } else {
+            revealing.set(Some(id));
+            select_tab(level as usize);
+        }
+    };
+    let label = move |id| subjects.describe(id).map(|(_, l)| l);
+
+    let collisions_view = move || {
+        let pairs = collisions();
+        (!pairs.is_empty()).then(|| {
+            let pairs = pairs
+                .into_iter()
+                .map(|(a, b)| {
+                    view! {
+                        <li>
+                            <button type="button" class="link" on:click=move |_| show(a)>
+                                {label(a)}
+                            </button>
+                            " ↔ "
+                            <button type="button" class="link" on:click=move |_| show(b)>
+                                {label(b)}
+                            </button>
+                        </li>
+                    }
+                })
+                .collect_view();
+            view! {
+                <div class="flex flex-wrap gap-1 items-center text-red-500">
+                    {icon!("mdi/alert-outline", "inline-block align-middle")}
+                    "Collisions: "
+                    <ul class="flex flex-wrap gap-2">{pairs}</ul>
+                </div>
+            }
+        })
+    };
+
+    let missing_view = move || {
+        let missing = missing();
+        (!missing.is_empty()).then(|| {
+            view! {
+                <span class="opacity-70">
+                    {format!("No choice yet: {}", missing.join(", "))}
+                </span>
+            }
+        })
+    };
+
+    let diff_view = move || {
+        let diff = diff();
+        let list = |prefix: &str, codes: Vec<String>| {
+            (!codes.is_empty()).then(|| {
+                codes
+                    .into_iter()
+                    .map(|c| format!("{prefix}{c}"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            })
+        };
+        (!diff.is_empty()).then(|| {
+            view! {
+                <span>
+                    "Unsaved: "
+                    <span class="text-green-600">{list("+", diff.added)}</span>
+                    " "
+                    <span class="text-red-500">{list("−", diff.dropped)}</span>
+                    " "
+                    <span class="text-indigo-500">{list("~", diff.switched)}</span>
+                </span>
+            }
+        })
+    };
+
+    view! {
+        <div class="mr-auto flex flex-wrap gap-x-4 gap-y-1 items-center text-sm">
+            <span>"Subjects: " <b>{count}</b></span>
+            <CreditLoad selected=subjects.selected_credits()/>
+            {collisions_view}
+            {missing_view}
+            {diff_view}
+        </div>
+    }
+}
+
+/// The outcome of the last save, with a way to retry a failed one
+#[component]
+pub fn SaveStatus(subjects: SubjectsSignal) -> impl IntoView {
+    use super::subjects_signal::SaveError;
+
+    let failed = move |e: SaveError| {
+        let retry = e.can_retry().then(|| {
+            view! {
+                <button type="button" class="link" on:click=move |_| subjects.save()>
+                    "Retry"
+                </button>
+            }
+        });
+        let unauthorized =
+            SaveError::Registration(RegistrationError::Unauthorized);
+        let login = (e == unauthorized).then(
+            || view! { <a class="link" href="/login">"Log in again"</a> },
+        );
+        view! {
+            <span class="text-sm text-red-500" role="alert">
+                {icon!("mdi/alert-outline", "inline-block align-middle mr-1")}
+                {e.to_string()}
+                " "
+                {retry}
+                {login}
+            </span>
+        }
+    };
+
+    let state = subjects.save_state();
+    move || match state() {
+        SaveState::Idle => ().into_view(),
+        SaveState::Pending => {
+            view! { <span class="text-sm opacity-70">"Saving…"</span> }
+                .into_view()
+        }
+        SaveState::Saved => view! {
+            <span class="text-sm text-green-600">
+                {icon!("mdi/check-circle-outline", "inline-block align-middle")}
+                "Saved"
+            </span>
+        }
+        .into_view(),
+        SaveState::Failed(e) => failed(e).into_view(),
+    }
+}
+
+/// Notices pushed by the server, until dismissed
+#[component]
+pub fn Notices(notices: RwSignal<Vec<Notice>>) -> impl IntoView {
+    let dismiss = move |i: usize| {
+        notices.update(|n| {
+            n.remove(i);
+        })
+    };
+    let notice = move |(i, notice): (usize, Notice)| {
+        let (icon, class) = match notice.level {
+            NoticeLevel::Info => (
+                icon!("mdi/information-outline", "inline-block align-middle"),
+                "text-indigo-500 dark:text-indigo-300",
+            ),
+            NoticeLevel::Warning => (
+                icon!("mdi/alert-outline", "inline-block align-middle"),
+                "text-red-500",
+            ),
+        };
+        view! {
+            <li class=format!("flex gap-2 items-center text-sm {class}") role="status">
+                {icon}
+                {notice.text}
+                <button
+                    type="button"
+                    aria-label="dismiss"
+                    class="ml-auto"
+                    on:click=move |_| dismiss(i)
+                >
+                    {icon!("mdi/close", "inline-block align-middle")}
+                </button>
+            </li>
+        }
+    };
+
+    move || {
+        let notices = notices();
+        (!notices.is_empty()).then(|| {
+            view! {
+                <ul class="mb-2 flex flex-col gap-1">
+                    {notices.into_iter().enumerate().map(notice).collect_view()}
+                </ul>
+            }
+        })
+    }
+}
+
+/// Shown while the live updates are down, with a way to reconnect without
+/// waiting for the next automatic attempt
+pub fn connection_status(
+    ready_state: Signal<ConnectionReadyState>,
+    reconnect: impl Fn() + Clone + 'static,
+) -> impl IntoView {
+    move || match ready_state() {
+        ConnectionReadyState::Open => ().into_view(),
+        ConnectionReadyState::Connecting => view! {
+            <p class="mb-2 text-sm opacity-70">
+                {icon!("mdi/lan-pending", "inline-block align-middle mr-1")}
+                "Connecting to live updates…"
+            </p>
+        }
+        .into_view(),
+        ConnectionReadyState::Closing | ConnectionReadyState::Closed => {
+            let reconnect = reconnect.clone();
+            view! {
+                <p class="mb-2 text-sm text-red-500" role="alert">
+                    {icon!("mdi/lan-disconnect", "inline-block align-middle mr-1")}
+                    "Live updates are disconnected, seats may be outdated. "
+                    <button type="button" class="link" on:click=move |_| reconnect()>
+                        "Reconnect"
+                    </button>
+                </p>
+            }
+            .into_view()
+        }
+    }
+}
+
+/// The selected credit hours against the range allowed to the student
+#[component]
+fn CreditLoad(selected: Signal<u32>) -> impl IntoView {
+    let limits = Resource::new(|| (), |_| get_credit_limits());
+
+    view! {
+        <SusErr resource=limits let:limits>
+            {
+                let limits = *limits;
+                let standing = match limits.standing {
+                    Standing::Regular => None,
+                    s => Some(format!(" ({s})")),
+                };
+                view! {
+                    <span
+                        class="data-[invalid]:text-red-500"
+                        data-invalid=move || {
+                            selected() != 0 && !limits.allows(selected())
+                        }
+                    >
+                        "Credit hours: "
+                        <b>{selected}</b>
+                        {format!(" / {}–{}", limits.min, limits.max)}
+                        {standing}
+                    </span>
+                }
+            }
+        </SusErr>
+    }
+}
@@ -1,10 +1,12 @@
 use leptos::*;
 use std::collections::{BTreeSet, HashMap};
 
+use super::add_drop::Change;
 use super::server_fns::{get_registerable_subjects, get_subbed_subjects};
+use super::validation::{RegistrationError, Violation};
 use super::{Subject, SubjectChoices, SubjectId};
 
-use crate::class::Class;
+use crate::class::{Class, WeekParity};
 use crate::components::suserr::TransErrs;
 
 #[derive(Debug)]
@@ -15,11 +17,76 @@ struct MapValue {
     subject_idx: usize,
 }
 
+/// The selected subjects occupying each period, along with the week parity
+/// of the occupying class
+type CollisionMap = [[Vec<(SubjectId, WeekParity)>; 12]; 6];
+
+/// The subjects a preset couldn't select a choice of, by subject code
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct PresetReport {
+    /// Subjects without a choice of the preset's group and section
+    pub missing: Vec<String>,
+    /// Subjects whose choice of the preset has no remaining seats
+    pub full: Vec<String>,
+}
+
+/// Changes to the selection since the last save, by subject code
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct SelectionDiff {
+    pub added: Vec<String>,
+    pub dropped: Vec<String>,
+    /// Subjects whose selected choice changed
+    pub switched: Vec<String>,
+}
+
+impl SelectionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.dropped.is_empty()
+            && self.switched.is_empty()
+    }
+}
+
+/// The state of the last save of the selection
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub enum SaveState {
+    #[default]
+    Idle,
+    Pending,
+    Saved,
+    Failed(SaveError),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SaveError {
+    /// The server refused the registration, nothing was saved
+    Registration(RegistrationError),
+    /// The request failed before the registration was processed
+    Server(String),
+}
+
+impl SaveError {
+    /// Returns true if saving the same selection again may succeed
+    pub fn can_retry(&self) -> bool {
+        matches!(self, Self::Server(_))
+    }
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Registration(e) => write!(f, "{e}"),
+            Self::Server(e) => write!(f, "Couldn't reach the server: {e}"),
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct SubjectsSignal {
     subject_map: RwSignal<HashMap<SubjectId, MapValue>>,
-    collision_map: RwSignal<[[Vec<SubjectId>; 12]; 6]>,
+    collision_map: RwSignal<CollisionMap>,
     subjects_choices: StoredValue<Vec<SubjectChoices>>,
+    save_state: RwSignal<SaveState>,
 }
 
 #[component]
@@ -27,7 +94,7 @@ pub fn CxtProvider<F>(children: F) -> impl IntoView
 where
     F: Fn(SubjectsSignal) -> Fragment + 'static,
 {
-    let all = Resource::new(|| (), |_| get_registerable_subjects());
+    let all = Resource::new(|| (), |_| get_registerable_subjects(true));
     let selected = Resource::new(|| (), |_| get_subbed_subjects());
 
     view! {
@@ -67,13 +134,18 @@ impl SubjectsSignal {
 
         // init collision map
         let collision_map = {
-            let mut map: [[Vec<SubjectId>; 12]; 6] = Default::default();
+            let mut map: CollisionMap = Default::default();
             for subject in selected {
-                let classes = &subjects.get(subject).unwrap().classes;
+                // choices of locked subjects aren't listed
+                let Some(MapValue { classes, .. }) = subjects.get(subject)
+                else {
+                    continue;
+                };
                 for class in classes {
                     let Class { day, period: (st, end), .. } = class;
-                    for i in *st..*end {
-                        map[*day as usize][i].push(*subject);
+                    let parity = class.ctype.week_parity();
+                    for i in *st..=*end {
+                        map[*day as usize][i].push((*subject, parity));
                     }
                 }
             }
@@ -84,28 +156,78 @@ impl SubjectsSignal {
             subject_map: RwSignal::new(subjects),
             collision_map,
             subjects_choices: StoredValue::new(all.to_vec()),
+            save_state: RwSignal::new(SaveState::Idle),
         }
     }
 
+    /// Sends the changes to the selection to the server, they're only marked
+    /// as saved once the server accepts them. Also used to retry a failed save
     pub fn save(self) {
-        use super::server_fns::register_subjects;
-        spawn_local(async move {
-            let selected = self.subject_map.with_untracked(|hm| {
-                hm.iter()
-                    .filter(|(_, v)| v.is_selected)
-                    .map(|(k, _)| *k)
+        use super::add_drop::apply_changes;
+
+        if self.save_state.get_untracked() == SaveState::Pending {
+            return;
+        }
+        let changes: Vec<_> = self.subject_map.with_untracked(|hm| {
+            self.subjects_choices.with_value(|choices| {
+                Self::changes(hm, choices)
+                    .into_iter()
+                    .map(|(_, change)| change)
                     .collect()
-            });
-            // TODO: handle errors (show error msg)
-            let _ = register_subjects(selected).await;
-            // if success
-            self.subject_map.update(|hm| {
-                hm.values_mut()
-                    .for_each(|v| v.initial_selected = v.is_selected);
-            });
+            })
+        });
+        self.save_state.set(SaveState::Pending);
+
+        spawn_local(async move {
+            let state = match apply_changes(changes.clone()).await {
+                Ok(Ok(())) => {
+                    // changes made while saving remain unsaved
+                    self.subject_map.update(|hm| {
+                        let mut mark = |id, registered| {
+                            if let Some(v) = hm.get_mut(&id) {
+                                v.initial_selected = registered;
+                            }
+                        };
+                        for change in &changes {
+                            if let Some(id) = change.removed() {
+                                mark(id, false);
+                            }
+                            if let Some(id) = change.added() {
+                                mark(id, true);
+                            }
+                        }
+                    });
+                    SaveState::Saved
+                }
+                Ok(Err(e)) => SaveState::Failed(SaveError::Registration(e)),
+                Err(e) => SaveState::Failed(SaveError::Server(e.to_string())),
+            };
+            self.save_state.set(state);
         })
     }
 
+    pub fn save_state(self) -> Signal<SaveState> {
+        self.save_state.into()
+    }
+
+    /// returns a signal of the rule violations of the choice reported by the
+    /// last failed save
+    pub fn violations(self, subject: SubjectId) -> Signal<Vec<Violation>> {
+        Memo::new(move |_| {
+            self.save_state.with(|state| match state {
+                SaveState::Failed(SaveError::Registration(
+                    RegistrationError::Rejected(v),
+                )) => v
+                    .iter()
+                    .filter(|v| v.subjects().contains(&subject))
+                    .cloned()
+                    .collect(),
+                _ => Vec::new(),
+            })
+        })
+        .into()
+    }
+
     pub fn saved(self) -> Signal<bool> {
         Memo::new(move |_| {
             self.subject_map.with(|hm| {
@@ -120,6 +242,9 @@ impl SubjectsSignal {
             m.values_mut()
                 .for_each(|v| v.is_selected = v.initial_selected);
         });
+        if self.save_state.get_untracked() != SaveState::Pending {
+            self.save_state.set(SaveState::Idle);
+        }
     }
 
     /// returns a signal that emits true if the subject is selected
@@ -175,8 +300,9 @@ impl SubjectsSignal {
 
                 for class in classes {
                     let Class { day, period: (st, end), .. } = *class;
-                    for i in st..end {
-                        col_map[day as usize][i].push(subject);
+                    let parity = class.ctype.week_parity();
+                    for i in st..=end {
+                        col_map[day as usize][i].push((subject, parity));
                     }
                 }
 
@@ -198,7 +324,7 @@ impl SubjectsSignal {
     fn deselect_helper(
         id: SubjectId,
         subjects: &mut HashMap<SubjectId, MapValue>,
-        col_map: &mut [[Vec<SubjectId>; 12]; 6],
+        col_map: &mut CollisionMap,
     ) {
         let classes = match subjects.get_mut(&id) {
             Some(MapValue { is_selected: false, .. }) | None => return,
@@ -210,8 +336,8 @@ impl SubjectsSignal {
 
         for class in classes {
             let Class { day, period: (st, end), .. } = *class;
-            for i in st..end {
-                col_map[day as usize][i].retain(|&el| el != id);
+            for i in st..=end {
+                col_map[day as usize][i].retain(|&(el, _)| el != id);
             }
         }
     }
@@ -238,19 +364,25 @@ impl SubjectsSignal {
             let class_idx: Vec<_> = classes
                 .iter()
                 .flat_map(|c| {
-                    (c.period.0..c.period.1).map(|i| (c.day as usize, i))
+                    let parity = c.ctype.week_parity();
+                    (c.period.0..=c.period.1)
+                        .map(move |i| (c.day as usize, i, parity))
                 })
                 .collect();
             Some(class_idx)
         });
 
+        // alternating classes (odd and even weeks) can share a period
         match class_idx {
             Some(class_idx) => Memo::new(move |_| {
                 self.collision_map.with(|col_map| {
-                    class_idx
-                        .iter()
-                        .map(|&(day, period)| &col_map[day][period])
-                        .any(|v| v.contains(&subject) && v.len() > 1)
+                    class_idx.iter().any(|&(day, period, parity)| {
+                        let v = &col_map[day][period];
+                        v.iter().any(|&(id, _)| id == subject)
+                            && v.iter().any(|&(id, p)| {
+                                id != subject && p.overlaps(&parity)
+                            })
+                    })
                 })
             })
             .into(),
@@ -258,6 +390,31 @@ impl SubjectsSignal {
         }
     }
 
+    /// Returns true if the classes of the subject overlap those of a selected
+    /// subject, other than its own choices, which selecting it would replace.
+    /// Tracks the selection
+    pub fn collides_with_selection(self, subject: SubjectId) -> bool {
+        self.subject_map.with(|hm| {
+            let Some(v) = hm.get(&subject) else {
+                return false;
+            };
+            let is_other = |id| {
+                hm.get(id)
+                    .is_some_and(|o: &MapValue| o.subject_idx != v.subject_idx)
+            };
+            self.collision_map.with(|col_map| {
+                v.classes.iter().any(|c| {
+                    let parity = c.ctype.week_parity();
+                    (c.period.0..=c.period.1).any(|i| {
+                        col_map[c.day as usize][i]
+                            .iter()
+                            .any(|(id, p)| is_other(id) && p.overlaps(&parity))
+                    })
+                })
+            })
+        })
+    }
+
     pub fn classes(self) -> Signal<Vec<Class>> {
         // PERF: this could be optimized with memos and stuff
         (move || {
@@ -271,6 +428,173 @@ impl SubjectsSignal {
         .into_signal()
     }
 
+    /// Selects the choice of `group` and `section` of every subject of `level`,
+    /// choices that `is_full` are skipped unless already selected
+    pub fn apply_preset(
+        self,
+        level: u8,
+        group: u8,
+        section: u8,
+        is_full: impl Fn(SubjectId) -> bool,
+    ) -> PresetReport {
+        let mut report = PresetReport::default();
+        let mut matching = Vec::new();
+        self.subjects_choices.with_value(|choices| {
+            let choices = choices.iter().filter(|sc| !sc.is_locked());
+            for sc in choices.filter(|sc| sc.level == level) {
+                let choice = sc
+                    .choices
+                    .iter()
+                    .find(|s| s.group == group && s.section == section);
+                match choice {
+                    None => report.missing.push(sc.code.clone()),
+                    Some(s)
+                        if is_full(s.id)
+                            && !self.is_selected_untracked(s.id) =>
+                    {
+                        report.full.push(sc.code.clone())
+                    }
+                    Some(s) => matching.push(s.id),
+                }
+            }
+        });
+
+        batch(|| matching.into_iter().for_each(|id| self.select(id)));
+        report
+    }
+
+    /// returns a signal of the total credit hours of the selected subjects
+    pub fn selected_credits(self) -> Signal<u32> {
+        Memo::new(move |_| {
+            self.subject_map.with(|hm| {
+                self.subjects_choices.with_value(|choices| {
+                    hm.values()
+                        .filter(|v| v.is_selected)
+                        .map(|v| choices[v.subject_idx].credit as u32)
+                        .sum()
+                })
+            })
+        })
+        .into()
+    }
+
+    /// returns a signal of the number of selected subjects
+    pub fn selected_count(self) -> Signal<usize> {
+        Memo::new(move |_| {
+            self.subject_map
+                .with(|hm| hm.values().filter(|v| v.is_selected).count())
+        })
+        .into()
+    }
+
+    /// returns a signal of every pair of selected choices sharing a period,
+    /// each pair is ordered and listed once
+    pub fn collisions(self) -> Signal<Vec<(SubjectId, SubjectId)>> {
+        Memo::new(move |_| {
+            self.collision_map.with(|col_map| {
+                let mut pairs = BTreeSet::new();
+                for cell in col_map.iter().flatten() {
+                    for (i, &(a, pa)) in cell.iter().enumerate() {
+                        for &(b, pb) in &cell[i + 1..] {
+                            if a != b && pa.overlaps(&pb) {
+                                pairs.insert((a.min(b), a.max(b)));
+                            }
+                        }
+                    }
+                }
+                pairs.into_iter().collect()
+            })
+        })
+        .into()
+    }
+
+    /// returns a signal of the changes to the selection since the last save
+    pub fn diff(self) -> Signal<SelectionDiff> {
+        Memo::new(move |_| {
+            self.subject_map.with(|hm| {
+                let mut diff = SelectionDiff::default();
+                self.subjects_choices.with_value(|choices| {
+                    for (i, change) in Self::changes(hm, choices) {
+                        let code = choices[i].code.clone();
+                        match change {
+                            Change::Add(_) => diff.added.push(code),
+                            Change::Drop(_) => diff.dropped.push(code),
+                            Change::Swap { .. } => diff.switched.push(code),
+                        }
+                    }
+                });
+                diff
+            })
+        })
+        .into()
+    }
+
+    /// The changes to the selection since the last save, along with the
+    /// index of the subject of each in `choices`
+    fn changes(
+        hm: &HashMap<SubjectId, MapValue>,
+        choices: &[SubjectChoices],
+    ) -> Vec<(usize, Change)> {
+        choices
+            .iter()
+            .enumerate()
+            .filter_map(|(i, sc)| {
+                let find = |initial: bool| {
+                    sc.choices.iter().map(|s| s.id).find(|id| {
+                        hm.get(id).is_some_and(|v| {
+                            if initial {
+                                v.initial_selected
+                            } else {
+                                v.is_selected
+                            }
+                        })
+                    })
+                };
+                let change = match (find(true), find(false)) {
+                    (None, Some(id)) => Change::Add(id),
+                    (Some(id), None) => Change::Drop(id),
+                    (Some(from), Some(to)) if from != to => {
+                        Change::Swap { from, to }
+                    }
+                    _ => return None,
+                };
+                Some((i, change))
+            })
+            .collect()
+    }
+
+    /// Returns the codes of the unlocked subjects of `level` without a
+    /// selected choice. Tracks the selection
+    pub fn missing_choice(self, level: u8) -> Vec<String> {
+        self.subject_map.with(|hm| {
+            self.subjects_choices.with_value(|choices| {
+                choices
+                    .iter()
+                    .filter(|sc| sc.level == level && !sc.is_locked())
+                    .filter(|sc| {
+                        !sc.choices.iter().any(|s| {
+                            hm.get(&s.id).is_some_and(|v| v.is_selected)
+                        })
+                    })
+                    .map(|sc| sc.code.clone())
+                    .collect()
+            })
+        })
+    }
+
+    /// Returns the level of the subject the choice belongs to, along with
+    /// a label of the choice, e.g. `CSE131 G2`
+    pub fn describe(self, id: SubjectId) -> Option<(u8, String)> {
+        let idx = self
+            .subject_map
+            .with_untracked(|hm| hm.get(&id).map(|v| v.subject_idx))?;
+        self.subjects_choices.with_value(|choices| {
+            let sc = &choices[idx];
+            let s = sc.choices.iter().find(|s| s.id == id)?;
+            Some((sc.level, format!("{} G{}", sc.code, s.group)))
+        })
+    }
+
     pub fn choices(self) -> StoredValue<Vec<SubjectChoices>> {
         self.subjects_choices
     }
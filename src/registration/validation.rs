@@ -0,0 +1,244 @@
+use serde::{Deserialize, Serialize};
+
+use super::SubjectId;
+
+#[cfg(feature = "ssr")]
+use {
+    super::credit_load::CreditLimits,
+    super::prereq::{PreReq, Record},
+    crate::class::TimeSlot,
+    std::collections::{BTreeSet, HashMap, HashSet},
+};
+
+/// Upper bound on the total credit hours of a single registration,
+/// for students whose bylaw has no credit-load policy
+#[cfg(feature = "ssr")]
+pub const MAX_CREDIT_HOURS: u32 = 21;
+
+/// A registration rule broken by a registration request
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum Violation {
+    /// The choice doesn't exist, or isn't offered
+    UnknownSubject(SubjectId),
+    /// Two choices of the same subject were selected
+    DuplicateSubject(SubjectId, SubjectId),
+    /// Two selected choices have overlapping classes
+    TimeCollision(SubjectId, SubjectId),
+    /// The choice has no remaining seats
+    NoSeats(SubjectId),
+    /// The subject was already passed by the student
+    AlreadyCompleted(SubjectId),
+    /// The subject is above the student's level
+    LevelTooHigh(SubjectId),
+    /// Descriptions of the pre-requisites the student doesn't meet
+    MissingPrerequisites {
+        subject: SubjectId,
+        missing: Vec<String>,
+    },
+    /// The stored pre-requisites of the subject are malformed
+    InvalidPrerequisites(SubjectId),
+    /// The total credit hours of the selection exceed the allowed maximum
+    CreditLimit { selected: u32, max: u32 },
+    /// The total credit hours of the selection are below the allowed minimum
+    CreditMinimum { selected: u32, min: u32 },
+}
+
+impl Violation {
+    /// The choices the violation is about, if any
+    pub fn subjects(&self) -> Vec<SubjectId> {
+        match *self {
+            Self::UnknownSubject(s)
+            | Self::NoSeats(s)
+            | Self::AlreadyCompleted(s)
+            | Self::LevelTooHigh(s)
+            | Self::MissingPrerequisites { subject: s, .. }
+            | Self::InvalidPrerequisites(s) => vec![s],
+            Self::DuplicateSubject(a, b) | Self::TimeCollision(a, b) => {
+                vec![a, b]
+            }
+            Self::CreditLimit { .. } | Self::CreditMinimum { .. } => Vec::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownSubject(s) => write!(f, "choice {s} is not offered"),
+            Self::DuplicateSubject(a, b) => {
+                write!(f, "choices {a} and {b} are of the same subject")
+            }
+            Self::TimeCollision(a, b) => {
+                write!(f, "choice {a} collides with choice {b}")
+            }
+            Self::NoSeats(s) => write!(f, "choice {s} has no remaining seats"),
+            Self::AlreadyCompleted(s) => {
+                write!(f, "choice {s} has already been passed")
+            }
+            Self::LevelTooHigh(s) => {
+                write!(f, "choice {s} is above the student's level")
+            }
+            Self::MissingPrerequisites { subject, missing } => {
+                write!(f, "choice {subject} requires {}", missing.join("; "))
+            }
+            Self::InvalidPrerequisites(s) => {
+                write!(f, "choice {s} has invalid pre-requisites")
+            }
+            Self::CreditLimit { selected, max } => write!(
+                f,
+                "{selected} credit hours selected, the maximum is {max}"
+            ),
+            Self::CreditMinimum { selected, min } => write!(
+                f,
+                "{selected} credit hours selected, the minimum is {min}"
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum RegistrationError {
+    Unauthorized,
+    /// The student's registration window isn't open
+    Closed,
+    /// The request broke one or more registration rules, nothing was saved
+    Rejected(Vec<Violation>),
+    /// Only full choices can be waitlisted
+    NotFull(SubjectId),
+    /// The student is already registered in the choice
+    AlreadyRegistered(SubjectId),
+    /// The student isn't registered in the choice
+    NotRegistered(SubjectId),
+    /// Only choices of the same subject can be swapped
+    InvalidSwap {
+        from: SubjectId,
+        to: SubjectId,
+    },
+}
+
+impl std::fmt::Display for RegistrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unauthorized => write!(f, "Unauthorized"),
+            Self::Closed => write!(f, "Registration is closed"),
+            Self::Rejected(v) => {
+                let v: Vec<_> = v.iter().map(|v| v.to_string()).collect();
+                write!(f, "Registration rejected: {}", v.join("; "))
+            }
+            Self::NotFull(s) => write!(f, "choice {s} still has seats"),
+            Self::AlreadyRegistered(s) => {
+                write!(f, "already registered in choice {s}")
+            }
+            Self::NotRegistered(s) => write!(f, "not registered in choice {s}"),
+            Self::InvalidSwap { from, to } => {
+                write!(f, "choices {from} and {to} are of different subjects")
+            }
+        }
+    }
+}
+
+pub type RegistrationResult = Result<(), RegistrationError>;
+
+/// A requested choice, along with everything needed to validate it
+#[cfg(feature = "ssr")]
+pub struct Candidate {
+    pub id: SubjectId,
+    /// `subjects.id` of the choice
+    pub subject_id: i64,
+    pub credit: u32,
+    pub level: u32,
+    /// Seats not taken by other students
+    pub rem_seats: i64,
+    /// false if the student was already registered in this choice
+    pub is_new: bool,
+    /// true if the student was already registered in a choice of the
+    /// subject, which exempts it from the eligibility rules
+    pub is_registered: bool,
+    /// `None` if the stored pre-requisites are malformed, the choice is
+    /// rejected until they're fixed
+    pub pre_req: Option<PreReq>,
+    pub slots: Vec<TimeSlot>,
+}
+
+/// Checks `candidates` against every registration rule
+/// `requested` are the ids sent by the client, `record` is the student's
+/// academic record, `codes` maps `subjects.id` to the subject code,
+/// `limits` bound the total credit hours of a non-empty selection, see
+/// `CreditLimits::without_minimum` for single changes
+#[cfg(feature = "ssr")]
+pub fn validate(
+    requested: &BTreeSet<SubjectId>,
+    candidates: &[Candidate],
+    record: &Record,
+    codes: &HashMap<i64, String>,
+    limits: &CreditLimits,
+) -> Vec<Violation> {
+    let mut violations: Vec<_> = requested
+        .iter()
+        .filter(|&id| candidates.iter().all(|c| c.id != *id))
+        .map(|&id| Violation::UnknownSubject(id))
+        .collect();
+
+    for (i, a) in candidates.iter().enumerate() {
+        for b in &candidates[i + 1..] {
+            if a.subject_id == b.subject_id {
+                violations.push(Violation::DuplicateSubject(a.id, b.id));
+            } else if a
+                .slots
+                .iter()
+                .any(|sa| b.slots.iter().any(|sb| sa.overlaps(sb)))
+            {
+                violations.push(Violation::TimeCollision(a.id, b.id));
+            }
+        }
+    }
+
+    // co-requisites can be taken alongside each other
+    let taking: HashSet<i64> =
+        candidates.iter().map(|c| c.subject_id).collect();
+    for c in candidates {
+        if c.is_new && c.rem_seats <= 0 {
+            violations.push(Violation::NoSeats(c.id));
+        }
+
+        // the record may have changed since the student registered
+        if c.is_registered {
+            continue;
+        }
+
+        if record.passed.contains_key(&c.subject_id) {
+            violations.push(Violation::AlreadyCompleted(c.id));
+        }
+
+        if c.level > record.level {
+            violations.push(Violation::LevelTooHigh(c.id));
+        }
+
+        match &c.pre_req {
+            Some(pre_req) => {
+                if let Some(unmet) = pre_req.unmet(record, &taking) {
+                    violations.push(Violation::MissingPrerequisites {
+                        subject: c.id,
+                        missing: unmet.describe(codes),
+                    });
+                }
+            }
+            None => violations.push(Violation::InvalidPrerequisites(c.id)),
+        }
+    }
+
+    // duplicate choices are only counted once
+    let selected: u32 = candidates
+        .iter()
+        .map(|c| (c.subject_id, c.credit))
+        .collect::<HashMap<_, _>>()
+        .values()
+        .sum();
+    if selected > limits.max {
+        violations.push(Violation::CreditLimit { selected, max: limits.max });
+    } else if selected < limits.min && !requested.is_empty() {
+        violations.push(Violation::CreditMinimum { selected, min: limits.min });
+    }
+
+    violations
+}
@@ -0,0 +1,391 @@
+use leptos::*;
+
+use super::validation::RegistrationResult;
+use super::SubjectId;
+
+#[cfg(feature = "ssr")]
+use {
+    super::validation::{RegistrationError, Violation},
+    crate::login::UserId,
+    std::collections::BTreeSet,
+};
+
+/// Returns the student's current registration, and the same registration with
+/// `choice` in place of any other choice of the same subject
+#[cfg(feature = "ssr")]
+async fn with_choice(
+    conn: &mut sqlx::SqliteConnection,
+    student_id: UserId,
+    choice: SubjectId,
+) -> sqlx::Result<(BTreeSet<SubjectId>, BTreeSet<SubjectId>)> {
+    let rows = sqlx::query!(
+        r#"
+            SELECT tsub.term_subject_id AS "id: SubjectId",
+                   ts.subject_id = (
+                       SELECT subject_id FROM term_subjects WHERE id = ?2
+                   ) AS "same_subject!: bool"
+            FROM term_subscribers AS tsub
+            INNER JOIN term_subjects AS ts ON ts.id = tsub.term_subject_id
+            WHERE tsub.student_id = ?1
+        "#,
+        student_id,
+        choice
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let prev = rows.iter().map(|r| r.id).collect();
+    let new = rows
+        .iter()
+        .filter(|r| !r.same_subject)
+        .map(|r| r.id)
+        .chain(std::iter::once(choice))
+        .collect();
+    Ok((prev, new))
+}
+
+/// Returns the student's position in the queue of every choice they're waiting for
+#[cfg(feature = "ssr")]
+pub(crate) async fn get_waitlist_positions(
+    student_id: UserId,
+    pool: &sqlx::SqlitePool,
+) -> sqlx::Result<Vec<(SubjectId, u32)>> {
+    let positions = sqlx::query!(
+        r#"
+            SELECT w.term_subject_id AS "id: SubjectId",
+                   (
+                       SELECT COUNT(*)
+                       FROM waitlist AS w2
+                       WHERE w2.term_subject_id = w.term_subject_id
+                         AND w2.id <= w.id
+                   ) AS "position!: u32"
+            FROM waitlist AS w
+            WHERE w.student_id = ?
+            ORDER BY w.id
+        "#,
+        student_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|r| (r.id, r.position))
+    .collect();
+
+    Ok(positions)
+}
+
+/// Registers waitlisted students in the seats available in `freed`, in queue order.
+/// Students whose registration would break a rule are skipped, keeping their place.
+/// Returns the choices whose seats changed.
+#[cfg(feature = "ssr")]
+pub(crate) async fn promote_waitlisted(
+    pool: &sqlx::SqlitePool,
+    mut freed: Vec<SubjectId>,
+) -> sqlx::Result<Vec<SubjectId>> {
+    use super::add_drop::{changes_tx, Change};
+    use super::credit_load::credit_limits;
+    use super::history::EventSource;
+    use super::prereq::student_record;
+    use super::rem_seats_ws::NoticeMsg;
+    use super::ws_protocol::{Notice, NoticeLevel};
+    use actix_broker::{Broker, SystemBroker};
+
+    let source = EventSource::system("Promoted from the waitlist");
+    let mut changed = Vec::new();
+    while let Some(choice) = freed.pop() {
+        let queue = sqlx::query_scalar!(
+            r#"
+                SELECT student_id AS "student_id: UserId"
+                FROM waitlist
+                WHERE term_subject_id = ?
+                ORDER BY id
+            "#,
+            choice
+        )
+        .fetch_all(pool)
+        .await?;
+
+        for student_id in queue {
+            let rem_seats = sqlx::query_scalar!(
+                r#"
+                    SELECT (max_seats - taken_seats) AS "rem_seats!: i64"
+                    FROM term_subjects
+                    WHERE id = ?
+                "#,
+                choice
+            )
+            .fetch_one(pool)
+            .await?;
+            if rem_seats <= 0 {
+                break;
+            }
+
+            let (prev, new) =
+                with_choice(&mut *pool.acquire().await?, student_id, choice)
+                    .await?;
+            // any other choice of the same subject is swapped for this one
+            let change = match prev.difference(&new).next() {
+                Some(&from) => Change::Swap { from, to: choice },
+                None => Change::Add(choice),
+            };
+            let limits =
+                credit_limits(pool, student_id).await?.without_minimum();
+            let record = student_record(pool, student_id).await?;
+            // `changes_tx` removes the student from the queue on success
+            if let Ok(diff) = changes_tx(
+                pool,
+                student_id,
+                &[change],
+                &limits,
+                &record,
+                &source,
+            )
+            .await?
+            {
+                // dropping a choice of the same subject frees a seat in turn
+                freed.extend(diff.iter().filter(|&&s| s != choice));
+                changed.extend(diff);
+
+                let label = sqlx::query_scalar!(
+                    r#"
+                        SELECT s.code || ' G' || ts.group_no AS "label!: String"
+                        FROM term_subjects AS ts
+                        INNER JOIN subjects AS s ON s.id = ts.subject_id
+                        WHERE ts.id = ?
+                    "#,
+                    choice
+                )
+                .fetch_one(pool)
+                .await?;
+                Broker::<SystemBroker>::issue_async(NoticeMsg {
+                    student_id,
+                    notice: Notice {
+                        level: NoticeLevel::Info,
+                        text: format!(
+                            "A seat was freed, you're now registered in {label}"
+                        ),
+                    },
+                });
+            }
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Queues the student for a seat in a full choice, once a seat is freed the
+/// student is registered in it, replacing any other choice of the same subject
+#[server]
+pub async fn join_waitlist(
+    id: SubjectId,
+) -> Result<RegistrationResult, ServerFnError> {
+    use super::credit_load::credit_limits;
+    use super::prereq::student_record;
+    use super::rem_seats_ws::WaitlistMsg;
+    use super::server_fns::check_registration;
+    use super::window::{window_state, WindowState};
+    use actix_broker::{Broker, SystemBroker};
+
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Ok(Err(RegistrationError::Unauthorized));
+    };
+
+    // waitlists close with registration, only drops are promoted afterwards
+    if window_state(&pool, Some(student_id)).await? != WindowState::Open {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::FORBIDDEN);
+        return Ok(Err(RegistrationError::Closed));
+    }
+
+    let limits = credit_limits(&pool, student_id).await?.without_minimum();
+    let record = student_record(&pool, student_id).await?;
+
+    // Starting with a write locks the db for the rest of the transaction,
+    // so the student can't register anything breaking the checks below
+    // before the row is committed
+    let mut tx = pool.begin().await?;
+    let inserted = sqlx::query!(
+        r#"
+            INSERT INTO waitlist (student_id, term_subject_id)
+            VALUES (?, ?)
+            ON CONFLICT DO NOTHING
+        "#,
+        student_id,
+        id
+    )
+    .execute(&mut *tx)
+    .await;
+    let unknown = || {
+        let v = vec![Violation::UnknownSubject(id)];
+        Ok(Err(RegistrationError::Rejected(v)))
+    };
+    match inserted {
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+            return unknown();
+        }
+        inserted => inserted?,
+    };
+
+    let rem_seats = sqlx::query_scalar!(
+        r#"
+            SELECT (max_seats - taken_seats) AS "rem_seats!: i64"
+            FROM term_subjects
+            WHERE id = ?
+        "#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    match rem_seats {
+        None => return unknown(),
+        Some(rem_seats) if rem_seats > 0 => {
+            return Ok(Err(RegistrationError::NotFull(id)))
+        }
+        _ => (),
+    }
+
+    let (prev, new) = with_choice(&mut *tx, student_id, id).await?;
+    if prev.contains(&id) {
+        return Ok(Err(RegistrationError::AlreadyRegistered(id)));
+    }
+
+    // the student should be registrable once a seat is freed
+    let mut violations =
+        check_registration(&mut *tx, &new, &prev, &limits, &record).await?;
+    violations.retain(|v| *v != Violation::NoSeats(id));
+    if !violations.is_empty() {
+        return Ok(Err(RegistrationError::Rejected(violations)));
+    }
+    tx.commit().await?;
+
+    Broker::<SystemBroker>::issue_async(WaitlistMsg {
+        students: vec![student_id],
+        choices: vec![id],
+    });
+    Ok(Ok(()))
+}
+
+#[server]
+pub async fn leave_waitlist(id: SubjectId) -> Result<(), ServerFnError> {
+    use super::rem_seats_ws::WaitlistMsg;
+    use actix_broker::{Broker, SystemBroker};
+
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Ok(());
+    };
+
+    sqlx::query!(
+        "DELETE FROM waitlist WHERE student_id = ? AND term_subject_id = ?",
+        student_id,
+        id
+    )
+    .execute(&pool)
+    .await?;
+
+    Broker::<SystemBroker>::issue_async(WaitlistMsg {
+        students: vec![student_id],
+        choices: vec![id],
+    });
+    Ok(())
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod test {
+    use sqlx::SqlitePool;
+
+    use super::{
+        get_waitlist_positions, promote_waitlisted, SubjectId, UserId,
+    };
+    use crate::registration::add_drop::test::seeded_pool;
+
+    async fn execute(pool: &SqlitePool, sql: &str) {
+        sqlx::query(sql).execute(pool).await.unwrap();
+    }
+
+    async fn positions(pool: &SqlitePool, student: i64) -> Vec<(i64, u32)> {
+        get_waitlist_positions(UserId::from(student), pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(id, pos)| (id.0, pos))
+            .collect()
+    }
+
+    async fn registered(pool: &SqlitePool, student: i64) -> Vec<i64> {
+        sqlx::query_scalar(
+            r#"
+                SELECT term_subject_id
+                FROM term_subscribers
+                WHERE student_id = ?
+                ORDER BY term_subject_id
+            "#,
+        )
+        .bind(student)
+        .fetch_all(pool)
+        .await
+        .unwrap()
+    }
+
+    #[actix_web::test]
+    async fn queue_order() {
+        let (pool, _db) = seeded_pool().await;
+        execute(
+            &pool,
+            r#"
+                INSERT INTO waitlist (student_id, term_subject_id)
+                VALUES (3, 2), (1, 2), (2, 2), (1, 1)
+            "#,
+        )
+        .await;
+
+        assert_eq!(positions(&pool, 3).await, [(2, 1)]);
+        assert_eq!(positions(&pool, 1).await, [(2, 2), (1, 1)]);
+        assert_eq!(positions(&pool, 2).await, [(2, 3)]);
+
+        // leaving moves everyone behind up
+        execute(&pool, "DELETE FROM waitlist WHERE student_id = 3").await;
+        assert_eq!(positions(&pool, 1).await, [(2, 1), (1, 1)]);
+        assert_eq!(positions(&pool, 2).await, [(2, 2)]);
+    }
+
+    #[actix_web::test]
+    async fn promotion() {
+        let (pool, _db) = seeded_pool().await;
+        // student 1 has the only seat of 2, student 2 waits for it while
+        // registered in 1, another section of the same subject
+        execute(
+            &pool,
+            r#"
+                INSERT INTO term_subscribers (student_id, term_subject_id)
+                VALUES (1, 2), (2, 1);
+                INSERT INTO waitlist (student_id, term_subject_id)
+                VALUES (2, 2), (3, 2);
+            "#,
+        )
+        .await;
+
+        let (a, b) = (SubjectId(1), SubjectId(2));
+        assert!(promote_waitlisted(&pool, vec![b]).await.unwrap().is_empty());
+        assert_eq!(positions(&pool, 2).await, [(2, 1)]);
+
+        execute(&pool, "DELETE FROM term_subscribers WHERE student_id = 1")
+            .await;
+        // the first in the queue is swapped to the freed seat, the seat it
+        // frees in turn has no queue
+        assert_eq!(promote_waitlisted(&pool, vec![b]).await.unwrap(), [a, b]);
+        assert_eq!(registered(&pool, 2).await, [2]);
+        assert!(positions(&pool, 2).await.is_empty());
+        assert!(registered(&pool, 3).await.is_empty());
+        assert_eq!(positions(&pool, 3).await, [(2, 1)]);
+    }
+}
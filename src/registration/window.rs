@@ -0,0 +1,159 @@
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "ssr")]
+use crate::login::UserId;
+
+/// The registration period of a student, timestamps are in unix seconds
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct RegistrationWindow {
+    pub term: String,
+    /// The start of the period, or the student's staggered start if any
+    pub opens_at: i64,
+    pub closes_at: i64,
+    /// Subjects can be added or dropped until this, equal to `closes_at`
+    /// if the period has no add/drop phase
+    pub add_drop_deadline: i64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WindowState {
+    NotOpen,
+    Open,
+    /// Registration closed, but subjects can still be added or dropped
+    AddDrop,
+    Closed,
+}
+
+impl WindowState {
+    /// Returns true if the student's registration can still be changed
+    pub fn accepts_changes(&self) -> bool {
+        matches!(self, Self::Open | Self::AddDrop)
+    }
+}
+
+impl RegistrationWindow {
+    pub fn state(&self, now: i64) -> WindowState {
+        if now < self.opens_at {
+            WindowState::NotOpen
+        } else if now < self.closes_at {
+            WindowState::Open
+        } else if now < self.add_drop_deadline {
+            WindowState::AddDrop
+        } else {
+            WindowState::Closed
+        }
+    }
+
+    /// The next time the state changes, if any
+    pub fn next_change(&self, now: i64) -> Option<i64> {
+        match self.state(now) {
+            WindowState::NotOpen => Some(self.opens_at),
+            WindowState::Open => Some(self.closes_at),
+            WindowState::AddDrop => Some(self.add_drop_deadline),
+            WindowState::Closed => None,
+        }
+    }
+}
+
+/// Returns the student's window of the current registration period,
+/// i.e. the earliest period that hasn't ended, or the last one that has
+#[cfg(feature = "ssr")]
+pub(crate) async fn registration_window(
+    pool: &sqlx::SqlitePool,
+    student_id: Option<UserId>,
+) -> sqlx::Result<Option<RegistrationWindow>> {
+    sqlx::query_as!(
+        RegistrationWindow,
+        r#"
+            SELECT rp.term,
+                   unixepoch(COALESCE(
+                       (
+                           SELECT st.opens_at
+                           FROM registration_start_times AS st
+                           WHERE st.period_id = rp.id
+                             AND (st.level IS NULL OR st.level = sp.level)
+                             AND (
+                                 st.program_id IS NULL
+                                 OR st.program_id = sp.program_id
+                             )
+                           ORDER BY (st.level IS NOT NULL)
+                                  + (st.program_id IS NOT NULL) DESC
+                           LIMIT 1
+                       ),
+                       rp.opens_at
+                   )) AS "opens_at!: i64",
+                   unixepoch(rp.closes_at) AS "closes_at!: i64",
+                   unixepoch(COALESCE(rp.add_drop_deadline, rp.closes_at))
+                       AS "add_drop_deadline!: i64"
+            FROM registration_periods AS rp
+            LEFT JOIN users AS u ON u.id = ?1
+            LEFT JOIN student_profile AS sp ON sp.id = u.profile_id
+            ORDER BY
+                unixepoch(COALESCE(rp.add_drop_deadline, rp.closes_at))
+                    < unixepoch('now'),
+                CASE
+                    WHEN unixepoch(COALESCE(rp.add_drop_deadline, rp.closes_at))
+                        < unixepoch('now')
+                    THEN -unixepoch(rp.closes_at)
+                    ELSE unixepoch(rp.opens_at)
+                END
+            LIMIT 1
+        "#,
+        student_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+/// Returns the current state of the student's registration window,
+/// `Closed` if there's no registration period
+#[cfg(feature = "ssr")]
+pub(crate) async fn window_state(
+    pool: &sqlx::SqlitePool,
+    student_id: Option<UserId>,
+) -> sqlx::Result<WindowState> {
+    Ok(registration_window(pool, student_id)
+        .await?
+        .map_or(WindowState::Closed, |w| w.state(crate::utils::unix_now())))
+}
+
+#[server(encoding = "GetJson")]
+pub async fn get_registration_window(
+) -> Result<Option<RegistrationWindow>, ServerFnError> {
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let student_id = crate::login::user_id_from_jwt(&req);
+    Ok(registration_window(&pool, student_id).await?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WINDOW: RegistrationWindow = RegistrationWindow {
+        term: String::new(),
+        opens_at: 100,
+        closes_at: 200,
+        add_drop_deadline: 300,
+    };
+
+    #[test]
+    fn state() {
+        use WindowState::*;
+        let states = [0, 100, 199, 200, 299, 300].map(|t| WINDOW.state(t));
+        assert_eq!(states, [NotOpen, Open, Open, AddDrop, AddDrop, Closed]);
+
+        // no add/drop phase
+        let window = RegistrationWindow { add_drop_deadline: 200, ..WINDOW };
+        assert_eq!(window.state(199), Open);
+        assert_eq!(window.state(200), Closed);
+    }
+
+    #[test]
+    fn next_change() {
+        let changes = [0, 100, 250, 300].map(|t| WINDOW.next_change(t));
+        assert_eq!(changes, [Some(100), Some(200), Some(300), None]);
+    }
+}
@@ -0,0 +1,112 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::window::RegistrationWindow;
+use super::SubjectId;
+
+/// Bumped on any change to the messages that older clients or servers
+/// can't read
+pub const VERSION: u32 = 1;
+
+/// Messages sent to the client over `/ws/rem_seats`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum ServerMsg {
+    /// Remaining seats of the subscribed choices, only those that changed
+    /// since they were last sent
+    Seats(Vec<(SubjectId, u32)>),
+    /// The student's position in every waitlist they're in
+    Waitlist(Vec<(SubjectId, u32)>),
+    /// The student's registration window, sent on connection and whenever
+    /// its state changes
+    Window(Option<RegistrationWindow>),
+    /// The session ended, the server closes the connection after this
+    Logout,
+    Notice(Notice),
+}
+
+/// Messages sent by the client over `/ws/rem_seats`
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum ClientMsg {
+    /// Sends the remaining seats of the choices, then any change to them
+    Subscribe(Vec<SubjectId>),
+    Unsubscribe(Vec<SubjectId>),
+}
+
+/// A message to show to the student
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Notice {
+    pub level: NoticeLevel,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum NoticeLevel {
+    Info,
+    Warning,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DecodeError {
+    /// The frame is of another version of the protocol
+    Version,
+    Malformed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Frame<T> {
+    v: u32,
+    msg: T,
+}
+
+/// The version is read on its own first, so frames of other versions are
+/// told apart from malformed ones
+#[derive(Deserialize)]
+struct Version {
+    v: u32,
+}
+
+pub fn encode<T: Serialize>(msg: &T) -> String {
+    serde_json::to_string(&Frame { v: VERSION, msg })
+        .expect("This should never fail")
+}
+
+pub fn decode<T: DeserializeOwned>(text: &str) -> Result<T, DecodeError> {
+    let Version { v } =
+        serde_json::from_str(text).map_err(|_| DecodeError::Malformed)?;
+    if v != VERSION {
+        return Err(DecodeError::Version);
+    }
+    serde_json::from_str::<Frame<T>>(text)
+        .map(|f| f.msg)
+        .map_err(|_| DecodeError::Malformed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let msg = ServerMsg::Seats(vec![(SubjectId(1), 3)]);
+        let text = encode(&msg);
+        assert_eq!(text, r#"{"v":1,"msg":{"type":"seats","data":[[1,3]]}}"#);
+        assert_eq!(decode(&text), Ok(msg));
+
+        assert_eq!(
+            decode::<ServerMsg>(r#"{"v":1,"msg":{"type":"logout"}}"#),
+            Ok(ServerMsg::Logout)
+        );
+        assert_eq!(
+            decode::<ClientMsg>(r#"{"v":2,"msg":{"type":"ping"}}"#),
+            Err(DecodeError::Version)
+        );
+        assert_eq!(
+            decode::<ClientMsg>(r#"{"v":1,"msg":{"type":"ping"}}"#),
+            Err(DecodeError::Malformed)
+        );
+        assert_eq!(decode::<ClientMsg>("[1, 3]"), Err(DecodeError::Malformed));
+    }
+}
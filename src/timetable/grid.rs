@@ -3,11 +3,19 @@ use leptos::*;
 use super::*;
 use crate::class::Class;
 
-#[derive(Clone, PartialEq)]
-enum TimetableCell {
+#[derive(Clone, PartialEq, Debug)]
+pub(super) enum TimetableCell {
     None,
     Join,
     Some(Class),
+    /// Bi-weekly classes sharing a slot, spanning `colspan` periods.
+    /// Weekly classes overlapping them are in both halves, a half with more
+    /// than one class has a collision
+    Split {
+        odd: Vec<Class>,
+        even: Vec<Class>,
+        colspan: usize,
+    },
 }
 
 // type GridSignal = (
@@ -20,15 +28,46 @@ fn create_grid_signal(classes: Vec<Class>) -> GridSignal {
     grid_from_classes(classes).map(|row| row.map(RwSignal::new))
 }
 
-fn grid_from_classes(classes: Vec<Class>) -> [[TimetableCell; 12]; 6] {
+pub(super) fn grid_from_classes(
+    mut classes: Vec<Class>,
+) -> [[TimetableCell; 12]; 6] {
     use std::array::from_fn;
     use TimetableCell as Cell;
     let mut timetable = from_fn(|_| from_fn(|_| Cell::None));
-    for s in classes {
-        let row = s.day as usize;
-        let st = s.period.0;
-        let end = s.period.1;
-        timetable[row][st] = Cell::Some(s);
+
+    // overlapping classes are merged into a single block
+    classes.sort_by_key(|c| (c.day as usize, c.period.0));
+    let mut blocks: Vec<(usize, (usize, usize), Vec<Class>)> = Vec::new();
+    for c in classes {
+        let row = c.day as usize;
+        match blocks.last_mut() {
+            Some((r, (_, end), block)) if *r == row && c.period.0 <= *end => {
+                *end = (*end).max(c.period.1);
+                block.push(c);
+            }
+            _ => blocks.push((row, c.period, vec![c])),
+        }
+    }
+
+    for (row, (st, end), mut block) in blocks {
+        let weekly = |c: &Class| c.ctype.week_parity() == WeekParity::Both;
+        timetable[row][st] = match block.pop() {
+            Some(c) if block.is_empty() && weekly(&c) => Cell::Some(c),
+            last => {
+                let (mut odd, mut even) = (Vec::new(), Vec::new());
+                for c in block.into_iter().chain(last) {
+                    match c.ctype.week_parity() {
+                        WeekParity::Odd => odd.push(c),
+                        WeekParity::Even => even.push(c),
+                        WeekParity::Both => {
+                            odd.push(c.clone());
+                            even.push(c);
+                        }
+                    }
+                }
+                Cell::Split { odd, even, colspan: end - st + 1 }
+            }
+        };
         for i in st + 1..=end {
             timetable[row][i] = Cell::Join;
         }
@@ -136,6 +175,41 @@ fn TimetableGridRow(
                     .show_code(show_code)
                     .build(),
             ),
+            Cell::Split { odd, even, colspan } => {
+                let half = move |classes: Vec<Class>| {
+                    let collision = classes.len() > 1;
+                    let class_info = move |class: Class| {
+                        view! {
+                            <div class=format!("p-1 flex-1 {}", bg_color(&class.ctype))>
+                                <ClassInfo
+                                    class=&class
+                                    is_grid=true
+                                    show_location=show_location
+                                    show_prof=show_prof
+                                    show_code=show_code
+                                />
+                            </div>
+                        }
+                    };
+                    view! {
+                        <div
+                            class="flex flex-1 data-[collision]:ring-2 data-[collision]:ring-inset data-[collision]:ring-red-500"
+                            data-collision=collision
+                        >
+                            {classes.into_iter().map(class_info).collect_view()}
+                        </div>
+                    }
+                };
+                view! {
+                    <td colspan=colspan class="p-0">
+                        <div class="flex flex-col h-full divide-y divide-dashed">
+                            {half(odd)}
+                            {half(even)}
+                        </div>
+                    </td>
+                }
+                .into_view()
+            }
         }
     };
 
@@ -158,38 +232,125 @@ pub fn TimetableCell<'a>(
     #[prop(default = true.into(), into)] show_location: MaybeSignal<bool>,
     #[prop(default = true.into(), into)] show_code: MaybeSignal<bool>,
 ) -> impl IntoView {
-    let style = if is_grid {
-        "block"
-    } else {
-        "before:content-['_-_']"
-    };
+    let bg_color = bg_color(&class.ctype);
+    view! {
+        <td colspan=colspan class=format!("p-1 {bg_color}")>
+            <ClassInfo
+                class
+                is_grid
+                show_location
+                show_prof
+                show_code
+            />
+        </td>
+    }
+}
 
-    let bg_color = match class.ctype {
+fn bg_color(ctype: &Type) -> &'static str {
+    match ctype {
         Type::Lecture { .. } => "dark:bg-red-900 bg-red-200",
         Type::Lab { .. } => "dark:bg-cyan-800 bg-cyan-200",
         Type::Tutorial { .. } => "dark:bg-gray-800 bg-gray-200",
+    }
+}
+
+/// The contents of a timetable cell
+#[component]
+fn ClassInfo<'a>(
+    class: &'a Class,
+    is_grid: bool,
+    show_prof: MaybeSignal<bool>,
+    show_location: MaybeSignal<bool>,
+    show_code: MaybeSignal<bool>,
+) -> impl IntoView {
+    let style = if is_grid {
+        "block"
+    } else {
+        "before:content-['_-_']"
     };
 
     let class = class.clone();
+    let week_parity = class.ctype.week_parity();
 
     view! {
-        <td colspan=colspan class=format!("p-1 {bg_color}")>
-            <span class="text-xs">{format!("[{}] ", class.ctype)}</span>
-            <Show when=show_code fallback=|| ()>
-                <span class="text-xs">{&class.code}</span>
-            </Show>
-            <span class=format!("font-bold {}", style)>{&class.name}</span>
-            <Show when=show_prof fallback=|| ()>
-                {if let Type::Lecture{prof} = &class.ctype {
-                    view! { <span class=format!("text-xs font-thin {}", style)>{prof}</span> }
-                        .into_view()
-                } else {
-                    ().into_view()
-                }}
-            </Show>
-            <Show when=show_location fallback=|| ()>
-                <span class="text-xs block">{class.location.to_string()}</span>
-            </Show>
-        </td>
+        <span class="text-xs">{format!("[{}] ", class.ctype)}</span>
+        {(week_parity != WeekParity::Both).then(|| view! {
+            <span class="text-xs italic">{format!("({week_parity}) ")}</span>
+        })}
+        <Show when=show_code fallback=|| ()>
+            <span class="text-xs">{&class.code}</span>
+        </Show>
+        <span class=format!("font-bold {}", style)>{&class.name}</span>
+        <Show when=show_prof fallback=|| ()>
+            {if let Type::Lecture{prof} = &class.ctype {
+                view! { <span class=format!("text-xs font-thin {}", style)>{prof}</span> }
+                    .into_view()
+            } else {
+                ().into_view()
+            }}
+        </Show>
+        <Show when=show_location fallback=|| ()>
+            <span class="text-xs block">{class.location.to_string()}</span>
+        </Show>
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::class::fixture::{self, by_parity};
+    use crate::class::WeekParity::{self, *};
+    use TimetableCell as Cell;
+
+    fn class(id: i64, st: usize, end: usize, week_parity: WeekParity) -> Class {
+        let ctype = by_parity(week_parity);
+        fixture::class(id, ctype, DayOfWeek::Sunday, (st, end))
+    }
+
+    fn ids(classes: &[Class]) -> Vec<String> {
+        classes.iter().map(|c| c.id.to_string()).collect()
+    }
+
+    fn split(cell: &Cell) -> (Vec<String>, Vec<String>, usize) {
+        match cell {
+            Cell::Split { odd, even, colspan } => {
+                (ids(odd), ids(even), *colspan)
+            }
+            cell => panic!("expected a split cell, got {cell:?}"),
+        }
+    }
+
+    #[test]
+    fn cells() {
+        let grid = grid_from_classes(vec![
+            class(1, 0, 1, Both),
+            class(2, 3, 3, Odd),
+            class(3, 3, 4, Even),
+        ]);
+        let row = &grid[DayOfWeek::Sunday as usize];
+        assert_eq!(row[0], Cell::Some(class(1, 0, 1, Both)));
+        assert_eq!(row[1], Cell::Join);
+        assert_eq!(row[2], Cell::None);
+        assert_eq!(split(&row[3]), (vec!["2".into()], vec!["3".into()], 2));
+        assert_eq!(row[4], Cell::Join);
+        assert!(grid[0].iter().all(|c| *c == Cell::None));
+    }
+
+    #[test]
+    fn collisions() {
+        let grid = grid_from_classes(vec![
+            class(1, 0, 0, Odd),
+            class(2, 0, 1, Odd),
+            // a weekly class overlapping bi-weekly ones
+            class(3, 4, 5, Both),
+            class(4, 5, 5, Even),
+        ]);
+        let row = &grid[DayOfWeek::Sunday as usize];
+        assert_eq!(split(&row[0]), (vec!["1".into(), "2".into()], vec![], 2));
+        assert_eq!(
+            split(&row[4]),
+            (vec!["3".into()], vec!["3".into(), "4".into()], 2)
+        );
+        assert_eq!(row[5], Cell::Join);
     }
 }
@@ -0,0 +1,333 @@
+#![cfg(feature = "ssr")]
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+
+use super::{PERIOD_END_TIME, PERIOD_START_TIME};
+use crate::calendar::{AcademicTerm, CalendarEvent};
+use crate::class::{Class, Type, WeekParity};
+use crate::login::UserId;
+
+const DATE_TIME_FMT: &str = "%Y%m%dT%H%M%S";
+const UTC_DATE_TIME_FMT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Classes are held in local time, which calendar apps must not read in the
+/// subscriber's own timezone
+const TZID: &str = "Africa/Cairo";
+
+/// The offsets of `TZID`, daylight saving time lasts from the last friday of
+/// april until the end of the last thursday of october. The end is a second
+/// early, since `24:00` isn't a valid time
+const TZ_RULES: [&str; 14] = [
+    "BEGIN:STANDARD",
+    "DTSTART:19701029T235959",
+    "RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1TH",
+    "TZOFFSETFROM:+0300",
+    "TZOFFSETTO:+0200",
+    "TZNAME:EET",
+    "END:STANDARD",
+    "BEGIN:DAYLIGHT",
+    "DTSTART:19700424T000000",
+    "RRULE:FREQ=YEARLY;BYMONTH=4;BYDAY=-1FR",
+    "TZOFFSETFROM:+0200",
+    "TZOFFSETTO:+0300",
+    "TZNAME:EEST",
+    "END:DAYLIGHT",
+];
+
+/// Escapes a TEXT value (RFC 5545 3.3.11)
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line to lines of at most 75 octets (RFC 5545 3.1)
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+fn period_time(time: &str) -> NaiveTime {
+    NaiveTime::parse_from_str(time, "%I:%M %p").expect("valid period time")
+}
+
+/// Returns the dates the class takes place on during `term`,
+/// ignoring holidays and exam periods
+fn occurrences(class: &Class, term: &AcademicTerm) -> Vec<NaiveDate> {
+    let parse = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok();
+    let (Some(week_one), Some(ends_on)) =
+        (parse(&term.week_one), parse(&term.ends_on))
+    else {
+        return Vec::new();
+    };
+
+    // `DayOfWeek` starts on saturday, the first week is odd
+    let weekday = (week_one.weekday().num_days_from_sunday() as i64 + 1) % 7;
+    let offset = (class.day as i64 - weekday).rem_euclid(7);
+    let (first, step) = match class.ctype.week_parity() {
+        WeekParity::Both => (offset, 7),
+        WeekParity::Odd => (offset, 14),
+        WeekParity::Even => (offset + 7, 14),
+    };
+
+    std::iter::successors(Some(week_one + Duration::days(first)), |d| {
+        Some(*d + Duration::days(step))
+    })
+    .take_while(|d| *d <= ends_on)
+    .collect()
+}
+
+/// Returns a VEVENT repeating the class throughout the term
+fn class_event(
+    class: &Class,
+    term: &AcademicTerm,
+    events: &[CalendarEvent],
+    now: NaiveDateTime,
+) -> String {
+    let dates = occurrences(class, term);
+    let Some(first) = dates.first() else {
+        return String::new();
+    };
+    let start = period_time(PERIOD_START_TIME[class.period.0]);
+    let end = period_time(PERIOD_END_TIME[class.period.1]);
+    let interval = match class.ctype.week_parity() {
+        WeekParity::Both => 1,
+        WeekParity::Odd | WeekParity::Even => 2,
+    };
+    // `UNTIL` must be in UTC for local start times, the midnight after the
+    // last class is before the next one in any offset
+    let until = *dates.last().unwrap_or(first) + Duration::days(1);
+
+    let mut description = class.ctype.to_string();
+    if let Type::Lecture { prof } = &class.ctype {
+        description = format!("{description}\n{prof}");
+    }
+
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_owned(),
+        format!("UID:{}-{}@uni_web", term.id, class.id),
+        format!("DTSTAMP:{}", now.format(UTC_DATE_TIME_FMT)),
+        format!(
+            "DTSTART;TZID={TZID}:{}",
+            first.and_time(start).format(DATE_TIME_FMT)
+        ),
+        format!(
+            "DTEND;TZID={TZID}:{}",
+            first.and_time(end).format(DATE_TIME_FMT)
+        ),
+        format!(
+            "RRULE:FREQ=WEEKLY;INTERVAL={interval};UNTIL={}",
+            until.and_time(NaiveTime::MIN).format(UTC_DATE_TIME_FMT)
+        ),
+        format!(
+            "SUMMARY:{}",
+            escape(&format!("{} {}", class.code, class.name))
+        ),
+        format!("LOCATION:{}", escape(&class.location.to_string())),
+        format!("DESCRIPTION:{}", escape(&description)),
+    ];
+
+    // no classes are held during holidays and exams
+    let cancelled = dates.iter().filter(|d| {
+        let d = d.to_string();
+        events.iter().any(|e| e.starts_on <= d && d <= e.ends_on)
+    });
+    for d in cancelled {
+        lines.push(format!(
+            "EXDATE;TZID={TZID}:{}",
+            d.and_time(start).format(DATE_TIME_FMT)
+        ));
+    }
+    lines.push("END:VEVENT".to_owned());
+
+    lines.iter().map(|l| fold(l)).collect()
+}
+
+/// Renders the weekly classes as an iCalendar bounded by the term dates
+pub fn timetable_ics(
+    classes: &[Class],
+    term: &AcademicTerm,
+    events: &[CalendarEvent],
+) -> String {
+    let now = chrono::Utc::now().naive_utc();
+    let mut ics = [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//Alexandria University//Timetable//EN",
+        "CALSCALE:GREGORIAN",
+    ]
+    .iter()
+    .map(|l| fold(l))
+    .collect::<String>();
+    ics.push_str(&fold(&format!("X-WR-CALNAME:{}", escape(&term.abs))));
+    ics.push_str(&fold("BEGIN:VTIMEZONE"));
+    ics.push_str(&fold(&format!("TZID:{TZID}")));
+    ics.extend(TZ_RULES.iter().map(|l| fold(l)));
+    ics.push_str(&fold("END:VTIMEZONE"));
+    for class in classes {
+        ics.push_str(&class_event(class, term, events, now));
+    }
+    ics.push_str(&fold("END:VCALENDAR"));
+    ics
+}
+
+async fn student_ics(
+    pool: &sqlx::SqlitePool,
+    student_id: UserId,
+) -> sqlx::Result<Option<String>> {
+    use crate::calendar::{current_or_next_term, term_events};
+
+    let Some(term) = current_or_next_term(pool).await? else {
+        return Ok(None);
+    };
+    let events = term_events(pool, term.id).await?;
+    let classes = super::student_classes(pool, student_id).await?;
+    Ok(Some(timetable_ics(&classes, &term, &events)))
+}
+
+fn ics_response(ics: Option<String>) -> HttpResponse {
+    match ics {
+        Some(ics) => HttpResponse::Ok()
+            .content_type("text/calendar; charset=utf-8")
+            .insert_header((
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"timetable.ics\"",
+            ))
+            .body(ics),
+        None => HttpResponse::NotFound().body("No term is scheduled"),
+    }
+}
+
+/// `GET /api/timetable.ics`, authenticated by the session cookie
+pub async fn timetable_ics_handler(
+    req: HttpRequest,
+    pool: web::Data<sqlx::SqlitePool>,
+) -> actix_web::Result<HttpResponse> {
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+    let ics = student_ics(&pool, student_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(ics_response(ics))
+}
+
+/// `GET /api/timetable/{token}.ics`, for calendar apps subscribing to the
+/// timetable without a session
+pub async fn timetable_feed_handler(
+    token: web::Path<String>,
+    pool: web::Data<sqlx::SqlitePool>,
+) -> actix_web::Result<HttpResponse> {
+    let token = token.into_inner();
+    let student_id = sqlx::query_scalar!(
+        r#"
+            SELECT student_id AS "student_id: UserId"
+            FROM calendar_tokens
+            WHERE token = ?
+        "#,
+        token
+    )
+    .fetch_optional(pool.get_ref())
+    .await
+    .map_err(actix_web::error::ErrorInternalServerError)?;
+
+    let Some(student_id) = student_id else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let ics = student_ics(&pool, student_id)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(ics_response(ics))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::class::fixture::{self, by_parity};
+    use crate::class::DayOfWeek::{self, *};
+    use crate::class::WeekParity::*;
+
+    fn class(day: DayOfWeek, week_parity: WeekParity) -> Class {
+        fixture::class(1, by_parity(week_parity), day, (0, 1))
+    }
+
+    fn term(week_one: &str, ends_on: &str) -> AcademicTerm {
+        AcademicTerm {
+            id: 1,
+            abs: "Fall 2023".into(),
+            starts_on: week_one.into(),
+            ends_on: ends_on.into(),
+            week_one: week_one.into(),
+        }
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(escape("a\\b;c,d\ne"), r"a\\b\;c\,d\ne");
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn folding() {
+        assert_eq!(fold("short"), "short\r\n");
+
+        let line = "x".repeat(160);
+        let folded = fold(&line);
+        let lines: Vec<_> = folded.trim_end().split("\r\n").collect();
+        assert_eq!(
+            lines.iter().map(|l| l.len()).collect::<Vec<_>>(),
+            [75, 75, 12]
+        );
+        assert_eq!(folded.replace("\r\n ", ""), format!("{line}\r\n"));
+
+        // multi-byte characters aren't split
+        let folded = fold(&"é".repeat(40));
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(folded.lines().next().unwrap().chars().count(), 37);
+    }
+
+    #[test]
+    fn occurrence_dates() {
+        // starts on a saturday, the first day of the week
+        let fall = term("2023-09-30", "2023-10-28");
+        let dates = |day, parity| {
+            occurrences(&class(day, parity), &fall)
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            dates(Saturday, Both),
+            [
+                "2023-09-30",
+                "2023-10-07",
+                "2023-10-14",
+                "2023-10-21",
+                "2023-10-28"
+            ]
+        );
+        assert_eq!(dates(Monday, Odd), ["2023-10-02", "2023-10-16"]);
+        assert_eq!(dates(Monday, Even), ["2023-10-09", "2023-10-23"]);
+
+        // a term starting mid-week wraps to the following week
+        let mid_week = term("2023-10-03", "2023-10-17");
+        let sunday = occurrences(&class(Sunday, Both), &mid_week);
+        assert_eq!(sunday.first().unwrap().to_string(), "2023-10-08");
+
+        let invalid = term("", "2023-10-17");
+        assert!(occurrences(&class(Sunday, Both), &invalid).is_empty());
+    }
+}
@@ -1,9 +1,12 @@
 pub mod grid;
+pub mod ical;
 mod list;
+pub mod print;
 
 use leptos::*;
 use strum_macros::{Display, EnumString};
 
+use crate::calendar::DayLookup;
 pub use crate::class::{Class, *};
 use crate::components::checkbox::Checkbox;
 use crate::components::suserr::TransErr;
@@ -46,21 +49,50 @@ pub struct TimetableFlags {
     pub show_loc: bool,
     pub show_prof: bool,
     pub show_code: bool,
+    /// Hide bi-weekly classes not taking place this week
+    pub this_week_only: bool,
 }
 
-#[server(encoding = "GetJson")]
-pub async fn get_std_classes() -> Result<Vec<Class>, ServerFnError> {
-    use crate::class::db::ClassRow;
-    use crate::login::user_id_from_jwt;
+impl TimetableFlags {
+    /// Reads the flags from the url query, missing flags are defaulted
+    pub fn from_query(query: &leptos_router::ParamsMap) -> Self {
+        fn get<T: std::str::FromStr>(
+            query: &leptos_router::ParamsMap,
+            key: &str,
+        ) -> Option<T> {
+            query.get(key).and_then(|v| v.parse().ok())
+        }
 
-    let res = expect_context::<leptos_actix::ResponseOptions>();
-    let req = expect_context::<actix_web::HttpRequest>();
-    let pool = crate::utils::extract_pool().await;
+        Self {
+            view: get(query, "view").unwrap_or_default(),
+            time_style: get(query, "time_style").unwrap_or_default(),
+            show_loc: get(query, "show_loc").unwrap_or(true),
+            show_prof: get(query, "show_prof").unwrap_or(true),
+            show_code: get(query, "show_code").unwrap_or(true),
+            this_week_only: get(query, "this_week").unwrap_or_default(),
+        }
+    }
+}
 
-    let Some(student_id) = user_id_from_jwt(&req) else {
-        res.set_status(actix_web::http::StatusCode::UNAUTHORIZED);
-        return Ok(vec![]);
-    };
+/// Keeps the classes taking place in weeks of `parity`, if any
+pub fn filter_week(
+    classes: &[Class],
+    parity: Option<WeekParity>,
+) -> Vec<Class> {
+    classes
+        .iter()
+        .filter(|c| parity.is_none_or(|p| c.ctype.week_parity().overlaps(&p)))
+        .cloned()
+        .collect()
+}
+
+/// Returns the weekly classes of the student, sorted by day and period
+#[cfg(feature = "ssr")]
+pub(crate) async fn student_classes(
+    pool: &sqlx::SqlitePool,
+    student_id: crate::login::UserId,
+) -> sqlx::Result<Vec<Class>> {
+    use crate::class::db::ClassRow;
 
     let classes_db = sqlx::query_as!(
         ClassRow,
@@ -69,13 +101,14 @@ pub async fn get_std_classes() -> Result<Vec<Class>, ServerFnError> {
                 classes_view.*
             FROM classes_view
             INNER JOIN term_subjects as ts
+                ON classes_view.id IN (ts.lec_id, ts.tut_id, ts.lab_id)
             INNER JOIN term_subscribers as tsub
                 ON ts.id = tsub.term_subject_id
             WHERE tsub.student_id = ?;
         "#,
         student_id
     )
-    .fetch_all(&pool)
+    .fetch_all(pool)
     .await?;
 
     let mut classes: Vec<Class> = classes_db
@@ -83,8 +116,6 @@ pub async fn get_std_classes() -> Result<Vec<Class>, ServerFnError> {
         .filter_map(|c| c.try_into().ok())
         .collect();
 
-    // TODO: check for conflicts (handle biweekly courses)
-
     classes.sort_by(|a, b| {
         let a = (a.day as usize, a.period.0);
         let b = (b.day as usize, b.period.0);
@@ -94,13 +125,161 @@ pub async fn get_std_classes() -> Result<Vec<Class>, ServerFnError> {
     Ok(classes)
 }
 
-// TODO: handle bi-weekly classes
+#[server(encoding = "GetJson")]
+pub async fn get_std_classes() -> Result<Vec<Class>, ServerFnError> {
+    use crate::login::user_id_from_jwt;
+
+    let res = expect_context::<leptos_actix::ResponseOptions>();
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = user_id_from_jwt(&req) else {
+        res.set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Ok(vec![]);
+    };
+
+    Ok(student_classes(&pool, student_id).await?)
+}
+
+/// Returns the current week of the term, if a term is running
+#[server(encoding = "GetJson")]
+pub async fn get_current_week() -> Result<Option<u32>, ServerFnError> {
+    use crate::calendar::{term_on, week_of};
+
+    let pool = crate::utils::extract_pool().await;
+    let today = crate::utils::today();
+
+    let term = term_on(&pool, today).await?;
+    Ok(term.and_then(|t| week_of(&t, today)))
+}
+
+/// Returns the secret token of the student's timetable feed, if they've
+/// created one
+#[server(encoding = "GetJson")]
+pub async fn get_calendar_token() -> Result<Option<String>, ServerFnError> {
+    use crate::login::user_id_from_jwt;
+
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Ok(None);
+    };
+
+    Ok(sqlx::query_scalar!(
+        "SELECT token FROM calendar_tokens WHERE student_id = ?",
+        student_id
+    )
+    .fetch_optional(&pool)
+    .await?)
+}
+
+/// Creates a new token for the student's timetable feed, replacing the
+/// existing one revokes its subscriptions
+#[server]
+pub async fn create_calendar_token() -> Result<(), ServerFnError> {
+    use crate::login::user_id_from_jwt;
+
+    let req = expect_context::<actix_web::HttpRequest>();
+    let pool = crate::utils::extract_pool().await;
+
+    let Some(student_id) = user_id_from_jwt(&req) else {
+        expect_context::<leptos_actix::ResponseOptions>()
+            .set_status(actix_web::http::StatusCode::UNAUTHORIZED);
+        return Err(ServerFnError::ServerError("Auth Error".into()));
+    };
+
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    sqlx::query!(
+        r#"
+            INSERT INTO calendar_tokens (student_id, token)
+            VALUES (?, ?)
+            ON CONFLICT (student_id) DO UPDATE
+            SET token = excluded.token, created_at = datetime('now')
+        "#,
+        student_id,
+        token
+    )
+    .execute(&pool)
+    .await?;
+    Ok(())
+}
+
+/// Links to download the timetable as an iCalendar file, or to subscribe to it
+#[component]
+fn CalendarExport() -> impl IntoView {
+    let create = create_server_action::<CreateCalendarToken>();
+    let token = create_resource(
+        move || create.version().get(),
+        |_| get_calendar_token(),
+    );
+
+    // the feed url must be absolute for calendar apps
+    let (host, set_host) = create_signal(String::new());
+    create_effect(move |_| {
+        set_host(window().location().host().unwrap_or_default())
+    });
+    let feed_url = move || {
+        let token = token.get()?.ok()?;
+        Some(token.map(|token| {
+            format!("webcal://{}/api/timetable/{token}.ics", host())
+        }))
+    };
+
+    view! {
+        <div class="flex flex-wrap gap-2 items-center py-2 text-sm">
+            <a class="btn-primary-outline" href="/api/timetable.ics" rel="external" download>
+                "Download .ics"
+            </a>
+            <Transition fallback=|| ()>
+                {move || feed_url().map(|url| match url {
+                    Some(url) => view! {
+                        <a class="btn-primary-outline" href=url.clone() rel="external">
+                            "Subscribe"
+                        </a>
+                        <input class="bg-secondary rounded px-1 flex-grow" readonly value=url/>
+                        <button
+                            class="btn-primary-outline"
+                            title="Revokes the current subscription link"
+                            on:click=move |_| create.dispatch(CreateCalendarToken {})
+                        >
+                            "Reset link"
+                        </button>
+                    }.into_view(),
+                    None => view! {
+                        <button
+                            class="btn-primary-outline"
+                            on:click=move |_| create.dispatch(CreateCalendarToken {})
+                        >
+                            "Create subscription link"
+                        </button>
+                    }.into_view(),
+                })}
+            </Transition>
+        </div>
+    }
+}
+
 #[component]
 pub fn TimetablePage() -> impl IntoView {
     let table_data =
         create_resource(|| (), |_| async move { get_std_classes().await });
+    let current_week =
+        create_resource(|| (), |_| async move { get_current_week().await });
     let (timetable_settings, flags) = timetable_settings_inner();
     let view = Memo::new(move |_| flags().view);
+    let current_week =
+        Memo::new(move |_| current_week.get().and_then(|w| w.ok().flatten()));
+    // `None` shows every class
+    let shown_parity = Memo::new(move |_| {
+        current_week()
+            .filter(|_| flags().this_week_only)
+            .map(WeekParity::of_week)
+    });
+    let this_week =
+        move |classes: &[Class]| filter_week(classes, shown_parity());
 
     // PERF: Investigate `template!{}`
     // FIXME: fix tailwind not grabbing dynamic styles
@@ -108,14 +287,82 @@ pub fn TimetablePage() -> impl IntoView {
     // TODO: annotate the settings menu
     view! {
         <div class="relative flex justify-between">
-            <h1 class="text-4xl">"Timetable"</h1>
+            <h1 class="text-4xl">
+                "Timetable"
+                {move || current_week().map(|w| view! {
+                    <span class="ml-2 text-base font-thin">
+                        {format!("Week {w} ({})", WeekParity::of_week(w))}
+                    </span>
+                })}
+            </h1>
             <TimetableSettings>{timetable_settings}</TimetableSettings>
         </div>
         <div class="w-auto overflow-x-auto pt-7">
             <TransErr resource=table_data let:classes>
                 {match view() {
-                    View::List => view! { <TimetableList data=classes.to_owned() flags=flags/> },
-                    View::Grid => view! { <TimetableGrid data=classes.to_owned() flags=flags/> },
+                    View::List => view! { <TimetableList data=this_week(classes) flags=flags/> },
+                    View::Grid => view! { <TimetableGrid data=this_week(classes) flags=flags/> },
+                }}
+            </TransErr>
+        </div>
+        <PrintLinks/>
+        <CalendarExport/>
+        <DayLookup/>
+    }
+}
+
+/// Links to the printable versions of the timetable, keeping the settings
+#[component]
+fn PrintLinks() -> impl IntoView {
+    let query = leptos_router::use_query_map();
+    let query = move || query.with(|q| q.to_query_string());
+
+    view! {
+        <div class="flex flex-wrap gap-2 items-center pt-2 text-sm">
+            <a class="btn-primary-outline" href=move || format!("/timetable/print{}", query()) target="_blank">
+                "Print"
+            </a>
+            <a class="btn-primary-outline" href=move || format!("/api/timetable.pdf{}", query()) rel="external" target="_blank">
+                "PDF"
+            </a>
+        </div>
+    }
+}
+
+/// A print optimized timetable, takes the same query as `TimetablePage`
+#[component]
+pub fn TimetablePrintPage() -> impl IntoView {
+    let table_data =
+        create_resource(|| (), |_| async move { get_std_classes().await });
+    let current_week =
+        create_resource(|| (), |_| async move { get_current_week().await });
+    let query_map = leptos_router::use_query_map();
+    let flags = Memo::new(move |_| query_map.with(TimetableFlags::from_query));
+    let shown_parity = Memo::new(move |_| {
+        current_week
+            .get()
+            .and_then(|w| w.ok().flatten())
+            .filter(|_| flags().this_week_only)
+            .map(WeekParity::of_week)
+    });
+    let this_week =
+        move |classes: &[Class]| filter_week(classes, shown_parity());
+
+    view! {
+        <div class="p-4 print:p-0 bg-white text-black">
+            <div class="flex justify-between items-center pb-4">
+                <h1 class="text-2xl">"Timetable"</h1>
+                <button
+                    class="btn-primary-outline print:hidden"
+                    on:click=|_| { let _ = window().print(); }
+                >
+                    "Print"
+                </button>
+            </div>
+            <TransErr resource=table_data let:classes>
+                {match flags().view {
+                    View::List => view! { <TimetableList data=this_week(classes) flags=flags/> },
+                    View::Grid => view! { <TimetableGrid data=this_week(classes) flags=flags/> },
                 }}
             </TransErr>
         </div>
@@ -173,26 +420,23 @@ fn TimetableSettings(children: Children) -> impl IntoView {
 fn timetable_settings_inner() -> (impl IntoView, Signal<TimetableFlags>) {
     use crate::utils::create_query_signal as query;
 
-    let (view, set_view) = query::<View>("view");
-    let (time_style, set_time_style) = query::<TimeStyle>("time_style");
-    let (show_loc, set_show_loc) = query::<bool>("show_loc");
-    let (show_prof, set_show_prof) = query::<bool>("show_prof");
-    let (show_code, set_show_code) = query::<bool>("show_code");
+    let (_, set_view) = query::<View>("view");
+    let (_, set_time_style) = query::<TimeStyle>("time_style");
+    let (_, set_show_loc) = query::<bool>("show_loc");
+    let (_, set_show_prof) = query::<bool>("show_prof");
+    let (_, set_show_code) = query::<bool>("show_code");
+    let (_, set_this_week_only) = query::<bool>("this_week");
 
     // TODO: save params
-    let flags = Memo::new(move |_| TimetableFlags {
-        view: view().unwrap_or_default(),
-        time_style: time_style().unwrap_or_default(),
-        show_loc: show_loc().unwrap_or(true),
-        show_prof: show_prof().unwrap_or(true),
-        show_code: show_code().unwrap_or(true),
-    });
+    let query_map = leptos_router::use_query_map();
+    let flags = Memo::new(move |_| query_map.with(TimetableFlags::from_query));
 
     let view = Memo::new(move |_| flags().view);
     let time_style = Memo::new(move |_| flags().time_style);
     let show_loc = Memo::new(move |_| flags().show_loc);
     let show_prof = Memo::new(move |_| flags().show_prof);
     let show_code = Memo::new(move |_| flags().show_code);
+    let this_week_only = Memo::new(move |_| flags().this_week_only);
 
     let on_view_change = move |_| {
         set_view(
@@ -217,6 +461,13 @@ fn timetable_settings_inner() -> (impl IntoView, Signal<TimetableFlags>) {
             <Checkbox id="code" getter=show_code setter=set_show_code>
                 "Display class code"
             </Checkbox>
+            <Checkbox
+                id="this_week"
+                getter=this_week_only
+                setter=set_this_week_only
+            >
+                "Display this week's classes only"
+            </Checkbox>
         </div>
         <div class="grid grid-cols-[min-content,_1fr] gap-x-2 content-start">
             <input
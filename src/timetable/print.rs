@@ -0,0 +1,320 @@
+#![cfg(feature = "ssr")]
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use printpdf::{
+    path::PaintMode, BuiltinFont, Color, IndirectFontRef, Mm, PdfDocument,
+    PdfDocumentReference, PdfLayerReference, Rect, Rgb,
+};
+
+use super::grid::{grid_from_classes, TimetableCell};
+use super::*;
+
+const PAGE_WIDTH: f32 = 297.0;
+const PAGE_HEIGHT: f32 = 210.0;
+const MARGIN: f32 = 10.0;
+const FONT_SIZE: f32 = 7.0;
+/// Height of a text line in mm at `FONT_SIZE`
+const LINE_HEIGHT: f32 = 3.0;
+
+struct Fonts {
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Color {
+    let c = |v: u8| v as f32 / 255.0;
+    Color::Rgb(Rgb::new(c(r), c(g), c(b), None))
+}
+
+/// Same colors as the light theme of `TimetableCell`
+fn bg_color(ctype: &Type) -> Color {
+    match ctype {
+        Type::Lecture { .. } => rgb(254, 202, 202),
+        Type::Lab { .. } => rgb(165, 243, 252),
+        Type::Tutorial { .. } => rgb(229, 231, 235),
+    }
+}
+
+/// Truncates `text` to roughly fit in `width` mm
+fn fit(text: &str, width: f32, font_size: f32) -> String {
+    // helvetica averages around half an em per character
+    let max = (width / (font_size * 0.5 * 0.3528)) as usize;
+    if text.chars().count() <= max {
+        text.to_owned()
+    } else {
+        let cut: String = text.chars().take(max.saturating_sub(3)).collect();
+        format!("{cut}...")
+    }
+}
+
+/// Draws a bordered box, `y` is the top edge
+fn draw_box(
+    layer: &PdfLayerReference,
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    fill: Color,
+) {
+    layer.set_fill_color(fill);
+    layer.set_outline_color(rgb(107, 114, 128));
+    layer.set_outline_thickness(0.5);
+    layer.add_rect(
+        Rect::new(Mm(x), Mm(y - h), Mm(x + w), Mm(y))
+            .with_mode(PaintMode::FillStroke),
+    );
+    layer.set_fill_color(rgb(0, 0, 0));
+}
+
+/// Writes `lines` top to bottom starting from the top edge `y`,
+/// the first line is bold, stops once out of `h`
+fn draw_lines(
+    layer: &PdfLayerReference,
+    fonts: &Fonts,
+    lines: &[String],
+    (x, y, w, h): (f32, f32, f32, f32),
+) {
+    for (i, line) in lines.iter().enumerate() {
+        let baseline = y - LINE_HEIGHT * (i + 1) as f32;
+        if baseline < y - h {
+            break;
+        }
+        let font = if i == 0 { &fonts.bold } else { &fonts.regular };
+        let text = fit(line, w - 2.0, FONT_SIZE);
+        layer.use_text(text, FONT_SIZE, Mm(x + 1.0), Mm(baseline), font);
+    }
+}
+
+/// The lines describing a class, honoring `flags`
+fn class_lines(class: &Class, flags: &TimetableFlags) -> Vec<String> {
+    let mut lines = vec![class.name.clone()];
+    let mut kind = format!("[{}]", class.ctype);
+    if class.ctype.week_parity() != WeekParity::Both {
+        kind = format!("{kind} ({})", class.ctype.week_parity());
+    }
+    if flags.show_code {
+        kind = format!("{kind} {}", class.code);
+    }
+    lines.push(kind);
+    if let (true, Type::Lecture { prof }) = (flags.show_prof, &class.ctype) {
+        lines.push(prof.clone());
+    }
+    if flags.show_loc {
+        lines.push(class.location.to_string());
+    }
+    lines
+}
+
+fn period_header(i: usize, time_style: TimeStyle) -> Vec<String> {
+    let times = format!("{} - {}", PERIOD_START_TIME[i], PERIOD_END_TIME[i]);
+    match time_style {
+        TimeStyle::Numbers => vec![(i + 1).to_string()],
+        TimeStyle::Times => vec![times],
+        TimeStyle::Both => vec![(i + 1).to_string(), times],
+    }
+}
+
+fn draw_grid(
+    layer: &PdfLayerReference,
+    fonts: &Fonts,
+    classes: &[Class],
+    flags: &TimetableFlags,
+) {
+    let day_width = 18.0;
+    let head_height = 10.0;
+    let col = (PAGE_WIDTH - 2.0 * MARGIN - day_width) / 12.0;
+    let row = (PAGE_HEIGHT - 3.0 * MARGIN - head_height) / 6.0;
+    let top = PAGE_HEIGHT - 2.0 * MARGIN;
+    let white = || rgb(255, 255, 255);
+
+    for i in 0..12 {
+        let x = MARGIN + day_width + col * i as f32;
+        draw_box(layer, x, top, col, head_height, white());
+        let lines = period_header(i, flags.time_style);
+        draw_lines(layer, fonts, &lines, (x, top, col, head_height));
+    }
+
+    let grid = grid_from_classes(classes.to_vec());
+    for (d, cells) in grid.iter().enumerate() {
+        let y = top - head_height - row * d as f32;
+        let day = DayOfWeek::from_repr(d).map(|d| d.short_name().to_owned());
+        draw_box(layer, MARGIN, y, day_width, row, white());
+        draw_lines(
+            layer,
+            fonts,
+            &Vec::from_iter(day),
+            (MARGIN, y, day_width, row),
+        );
+
+        for (p, cell) in cells.iter().enumerate() {
+            let x = MARGIN + day_width + col * p as f32;
+            match cell {
+                TimetableCell::None => draw_box(layer, x, y, col, row, white()),
+                TimetableCell::Join => (),
+                TimetableCell::Some(class) => {
+                    let w = col * (class.period.1 - class.period.0 + 1) as f32;
+                    draw_box(layer, x, y, w, row, bg_color(&class.ctype));
+                    let lines = class_lines(class, flags);
+                    draw_lines(layer, fonts, &lines, (x, y, w, row));
+                }
+                TimetableCell::Split { odd, even, colspan } => {
+                    let w = col * *colspan as f32;
+                    let h = row / 2.0;
+                    for (i, classes) in [odd, even].into_iter().enumerate() {
+                        let y = y - h * i as f32;
+                        if classes.is_empty() {
+                            draw_box(layer, x, y, w, h, white());
+                        }
+                        // colliding classes share the half side by side
+                        let w = w / classes.len().max(1) as f32;
+                        for (j, class) in classes.iter().enumerate() {
+                            let x = x + w * j as f32;
+                            draw_box(layer, x, y, w, h, bg_color(&class.ctype));
+                            let lines = class_lines(class, flags);
+                            draw_lines(layer, fonts, &lines, (x, y, w, h));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Draws the list view, adding pages as needed
+fn draw_list(
+    doc: &PdfDocumentReference,
+    mut layer: PdfLayerReference,
+    fonts: &Fonts,
+    classes: &[Class],
+    flags: &TimetableFlags,
+) {
+    let day_width = 25.0;
+    let time_width = match flags.time_style {
+        TimeStyle::Numbers => 0.0,
+        _ => 35.0,
+    };
+    let period_width = match flags.time_style {
+        TimeStyle::Times => 0.0,
+        _ => 15.0,
+    };
+    let class_width =
+        PAGE_WIDTH - 2.0 * MARGIN - day_width - time_width - period_width;
+    let white = || rgb(255, 255, 255);
+
+    let mut y = PAGE_HEIGHT - 2.0 * MARGIN;
+    for class in classes {
+        let lines = class_lines(class, flags);
+        let h = LINE_HEIGHT * lines.len() as f32 + 2.0;
+        if y - h < MARGIN {
+            let (page, new_layer) =
+                doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Timetable");
+            layer = doc.get_page(page).get_layer(new_layer);
+            y = PAGE_HEIGHT - MARGIN;
+        }
+
+        let mut x = MARGIN;
+        let mut column = |w: f32, fill: Color, lines: &[String]| {
+            if w > 0.0 {
+                draw_box(&layer, x, y, w, h, fill);
+                draw_lines(&layer, fonts, lines, (x, y, w, h));
+                x += w;
+            }
+        };
+        column(day_width, white(), &[class.day.to_string()]);
+        column(
+            time_width,
+            white(),
+            &[format!(
+                "{} - {}",
+                PERIOD_START_TIME[class.period.0],
+                PERIOD_END_TIME[class.period.1]
+            )],
+        );
+        let (st, end) = (class.period.0 + 1, class.period.1 + 1);
+        let periods = match st == end {
+            true => st.to_string(),
+            false => format!("{st} - {end}"),
+        };
+        column(period_width, white(), &[periods]);
+        column(class_width, bg_color(&class.ctype), &lines);
+        y -= h;
+    }
+}
+
+/// Renders the timetable as an A4 landscape PDF
+pub fn timetable_pdf(
+    title: &str,
+    classes: &[Class],
+    flags: &TimetableFlags,
+) -> Result<Vec<u8>, printpdf::Error> {
+    let (doc, page, layer) =
+        PdfDocument::new(title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Timetable");
+    let fonts = Fonts {
+        regular: doc.add_builtin_font(BuiltinFont::Helvetica)?,
+        bold: doc.add_builtin_font(BuiltinFont::HelveticaBold)?,
+    };
+    let layer = doc.get_page(page).get_layer(layer);
+
+    layer.use_text(
+        title,
+        14.0,
+        Mm(MARGIN),
+        Mm(PAGE_HEIGHT - MARGIN - 5.0),
+        &fonts.bold,
+    );
+    match flags.view {
+        View::Grid => draw_grid(&layer, &fonts, classes, flags),
+        View::List => draw_list(&doc, layer, &fonts, classes, flags),
+    }
+
+    doc.save_to_bytes()
+}
+
+/// `GET /api/timetable.pdf`, takes the same query as the timetable page
+pub async fn timetable_pdf_handler(
+    req: HttpRequest,
+    pool: web::Data<sqlx::SqlitePool>,
+    query: web::Query<std::collections::HashMap<String, String>>,
+) -> actix_web::Result<HttpResponse> {
+    use crate::calendar::{term_on, week_of};
+    use actix_web::error::ErrorInternalServerError;
+
+    let Some(student_id) = crate::login::user_id_from_jwt(&req) else {
+        return Ok(HttpResponse::Unauthorized().finish());
+    };
+
+    let mut params = leptos_router::ParamsMap::new();
+    for (k, v) in query.into_inner() {
+        params.insert(k, v);
+    }
+    let flags = TimetableFlags::from_query(&params);
+
+    let classes = student_classes(&pool, student_id)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let today = crate::utils::today();
+    let term = term_on(&pool, today)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    let week = term.as_ref().and_then(|t| week_of(t, today));
+    let classes = match (flags.this_week_only, week) {
+        (true, Some(week)) => {
+            filter_week(&classes, Some(WeekParity::of_week(week)))
+        }
+        _ => classes,
+    };
+
+    let title = match term {
+        Some(term) => format!("Timetable - {}", term.abs),
+        None => "Timetable".to_owned(),
+    };
+    let pdf = timetable_pdf(&title, &classes, &flags)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            "inline; filename=\"timetable.pdf\"",
+        ))
+        .body(pdf))
+}
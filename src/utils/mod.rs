@@ -14,6 +14,20 @@ pub async fn extract_pool() -> sqlx::SqlitePool {
     data.get_ref().clone()
 }
 
+/// Returns the current unix timestamp in seconds
+pub fn unix_now() -> i64 {
+    #[cfg(feature = "ssr")]
+    return chrono::Utc::now().timestamp();
+    #[cfg(not(feature = "ssr"))]
+    return (js_sys::Date::now() / 1000.0) as i64;
+}
+
+/// Returns today's date, in UTC like `unix_now`
+#[cfg(feature = "ssr")]
+pub fn today() -> chrono::NaiveDate {
+    chrono::Utc::now().date_naive()
+}
+
 /// same as `leptos_router::create_query_signal` but with `NavigateOptions::replace = true`
 pub fn create_query_signal<T>(
     key: impl Into<Oco<'static, str>>,